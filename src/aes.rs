@@ -0,0 +1,238 @@
+use crate::Compression;
+use aes::{Aes128, Aes192, Aes256};
+use ctr::{
+	cipher::{KeyIvInit, StreamCipher},
+	Ctr128LE,
+};
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+use std::io::{self, Write};
+#[cfg(feature = "tokio")]
+use std::{
+	pin::Pin,
+	task::{ready, Context, Poll},
+};
+#[cfg(feature = "tokio")]
+use tokio::io::AsyncWrite;
+
+const AUTHENTICATION_CODE_SIZE: usize = 10;
+const ITERATIONS: u32 = 1000;
+pub(crate) const METHOD: u16 = 99;
+
+#[derive(Clone)]
+pub enum AesStrength {
+	Aes128,
+	Aes192,
+	Aes256,
+}
+
+impl AesStrength {
+	fn key_size(&self) -> usize {
+		match self {
+			Self::Aes128 => 16,
+			Self::Aes192 => 24,
+			Self::Aes256 => 32,
+		}
+	}
+
+	fn to_byte(&self) -> u8 {
+		match self {
+			Self::Aes128 => 0x01,
+			Self::Aes192 => 0x02,
+			Self::Aes256 => 0x03,
+		}
+	}
+}
+
+#[derive(Clone)]
+pub enum AesVersion {
+	Ae1,
+	Ae2,
+}
+
+pub(crate) fn extra_field(
+	strength: &AesStrength,
+	version: &AesVersion,
+	compression: &Compression,
+) -> Vec<u8> {
+	let mut field = Vec::with_capacity(11);
+	field.extend_from_slice(&[0x01, 0x99, 0x07, 0x00]);
+	field.extend_from_slice(match version {
+		AesVersion::Ae1 => &[0x01, 0x00],
+		AesVersion::Ae2 => &[0x02, 0x00],
+	});
+	field.extend_from_slice(b"AE");
+	field.push(strength.to_byte());
+	field.extend_from_slice(&compression.method().to_le_bytes());
+	field
+}
+
+enum Cipher {
+	Aes128(Ctr128LE<Aes128>),
+	Aes192(Ctr128LE<Aes192>),
+	Aes256(Ctr128LE<Aes256>),
+}
+
+impl Cipher {
+	fn apply_keystream(&mut self, data: &mut [u8]) {
+		match self {
+			Self::Aes128(cipher) => cipher.apply_keystream(data),
+			Self::Aes192(cipher) => cipher.apply_keystream(data),
+			Self::Aes256(cipher) => cipher.apply_keystream(data),
+		}
+	}
+}
+
+// Ciphertext not yet accepted by the inner writer stays in the buffer, so that the same type can
+// back both the blocking and the asynchronous writer.
+pub(crate) struct AesWriter<W> {
+	buffer: Vec<u8>,
+	cipher: Box<Cipher>,
+	mac: Hmac<Sha1>,
+	position: usize,
+	size: u64,
+	version: AesVersion,
+	writer: W,
+}
+
+impl<W> AesWriter<W> {
+	pub fn new(
+		writer: W,
+		password: &str,
+		strength: &AesStrength,
+		version: AesVersion,
+	) -> io::Result<Self> {
+		let key_size = strength.key_size();
+		let mut salt = vec![0; key_size / 2];
+		getrandom::getrandom(&mut salt)?;
+		let mut key = vec![0; 2 * key_size + 2];
+		pbkdf2::pbkdf2_hmac::<Sha1>(password.as_bytes(), &salt, ITERATIONS, &mut key);
+		let mut iv = [0; 16];
+		iv[0] = 1;
+		let (encryption_key, rest) = key.split_at(key_size);
+		let (authentication_key, verifier) = rest.split_at(key_size);
+		let cipher = Box::new(match strength {
+			AesStrength::Aes128 => Cipher::Aes128(Ctr128LE::new(encryption_key.into(), &iv.into())),
+			AesStrength::Aes192 => Cipher::Aes192(Ctr128LE::new(encryption_key.into(), &iv.into())),
+			AesStrength::Aes256 => Cipher::Aes256(Ctr128LE::new(encryption_key.into(), &iv.into())),
+		});
+		let Ok(mac) = Hmac::new_from_slice(authentication_key) else {
+			unreachable!();
+		};
+		let mut buffer = salt;
+		buffer.extend_from_slice(verifier);
+
+		Ok(Self {
+			size: buffer.len() as u64,
+			buffer,
+			cipher,
+			mac,
+			position: 0,
+			version,
+			writer,
+		})
+	}
+
+	pub fn hides_crc(&self) -> bool {
+		matches!(self.version, AesVersion::Ae2)
+	}
+
+	// The returned size covers salt, verifier, encrypted data and authentication code.
+	pub fn into_parts(self) -> (W, [u8; AUTHENTICATION_CODE_SIZE], u64) {
+		debug_assert_eq!(self.position, self.buffer.len());
+		let mut code = [0; AUTHENTICATION_CODE_SIZE];
+		code.copy_from_slice(&self.mac.finalize().into_bytes()[..AUTHENTICATION_CODE_SIZE]);
+		(
+			self.writer,
+			code,
+			self.size + AUTHENTICATION_CODE_SIZE as u64,
+		)
+	}
+
+	fn encrypt(&mut self, data: &[u8]) {
+		let start = self.buffer.len();
+		self.buffer.extend_from_slice(data);
+		self.cipher.apply_keystream(&mut self.buffer[start..]);
+		self.mac.update(&self.buffer[start..]);
+		self.size += data.len() as u64;
+	}
+
+	fn consume(&mut self, size: usize) {
+		self.position += size;
+		if self.position == self.buffer.len() {
+			self.buffer.clear();
+			self.position = 0;
+		}
+	}
+}
+
+impl<W: Write> AesWriter<W> {
+	fn drain(&mut self) -> io::Result<()> {
+		while self.position < self.buffer.len() {
+			match self.writer.write(&self.buffer[self.position..]) {
+				Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+				Ok(size) => self.consume(size),
+				Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+				Err(error) => return Err(error),
+			}
+		}
+		Ok(())
+	}
+}
+
+// Data is only encrypted once the previous data has been written, so that a failed write can be
+// retried with the same data.
+impl<W: Write> Write for AesWriter<W> {
+	fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+		self.drain()?;
+		self.encrypt(data);
+		Ok(data.len())
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.drain()?;
+		self.writer.flush()
+	}
+}
+
+#[cfg(feature = "tokio")]
+impl<W: AsyncWrite + Unpin> AesWriter<W> {
+	fn poll_drain(&mut self, context: &mut Context<'_>) -> Poll<io::Result<()>> {
+		while self.position < self.buffer.len() {
+			let size = ready!(
+				Pin::new(&mut self.writer).poll_write(context, &self.buffer[self.position..])
+			)?;
+			if size == 0 {
+				return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+			}
+			self.consume(size);
+		}
+		Poll::Ready(Ok(()))
+	}
+}
+
+#[cfg(feature = "tokio")]
+impl<W: AsyncWrite + Unpin> AsyncWrite for AesWriter<W> {
+	fn poll_write(
+		self: Pin<&mut Self>,
+		context: &mut Context<'_>,
+		data: &[u8],
+	) -> Poll<io::Result<usize>> {
+		let this = self.get_mut();
+		ready!(this.poll_drain(context))?;
+		this.encrypt(data);
+		Poll::Ready(Ok(data.len()))
+	}
+
+	fn poll_flush(self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<io::Result<()>> {
+		let this = self.get_mut();
+		ready!(this.poll_drain(context))?;
+		Pin::new(&mut this.writer).poll_flush(context)
+	}
+
+	// The authentication code still has to be written after the encrypted data, so the inner
+	// writer is only flushed.
+	fn poll_shutdown(self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<io::Result<()>> {
+		self.poll_flush(context)
+	}
+}
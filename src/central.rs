@@ -0,0 +1,146 @@
+use crate::{
+	cp437, DateTime, Entry, Error, CENTRAL_DIRECTORY_HEADER, END_CENTRAL_DIRECTORY, FLAG_UTF8,
+};
+use std::io::{Read, Seek, SeekFrom};
+
+// The end of central directory record is 22 bytes long, followed by a comment of up to 65535
+// bytes.
+const MAX_END_SIZE: u64 = 22 + u16::MAX as u64;
+
+pub(crate) struct End {
+	pub(crate) comment: Vec<u8>,
+	pub(crate) entries: u16,
+	pub(crate) offset: u64,
+	pub(crate) size: u64,
+}
+
+pub(crate) fn read_u16(data: &[u8], offset: usize) -> u16 {
+	u16::from_le_bytes(data[offset..offset + 2].try_into().unwrap())
+}
+
+pub(crate) fn read_u32(data: &[u8], offset: usize) -> u32 {
+	u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+// The tail of the archive is searched backwards, as the comment could contain the signature.
+// `start` is the position of the tail in the archive.
+fn find_end(tail: &[u8], start: u64) -> Result<End, Error> {
+	let position = (0..=tail.len().saturating_sub(22))
+		.rev()
+		.find(|&i| {
+			tail[i..].starts_with(END_CENTRAL_DIRECTORY)
+				&& 22 + usize::from(read_u16(tail, i + 20)) <= tail.len() - i
+		})
+		.ok_or(Error::InvalidArchive("end of central directory not found"))?;
+	let end = &tail[position..];
+	if read_u16(end, 4) != 0 || read_u16(end, 6) != 0 || read_u16(end, 8) != read_u16(end, 10) {
+		return Err(Error::Unsupported("split archives"));
+	}
+	let (size, offset) = (read_u32(end, 12), read_u32(end, 16));
+	if size == u32::MAX || offset == u32::MAX {
+		return Err(Error::Unsupported("ZIP64 archives"));
+	}
+	if u64::from(offset) + u64::from(size) > start + position as u64 {
+		return Err(Error::InvalidArchive("central directory is out of bounds"));
+	}
+	Ok(End {
+		comment: end[22..22 + usize::from(read_u16(end, 20))].to_vec(),
+		entries: read_u16(end, 10),
+		offset: offset.into(),
+		size: size.into(),
+	})
+}
+
+fn parse(mut data: &[u8], count: u16) -> Result<Vec<Entry>, Error> {
+	let mut entries = Vec::with_capacity(count.into());
+	for _ in 0..count {
+		if data.len() < 46 || !data.starts_with(CENTRAL_DIRECTORY_HEADER) {
+			return Err(Error::InvalidArchive("central directory header expected"));
+		}
+		let name_end = 46 + usize::from(read_u16(data, 28));
+		let extra_field_end = name_end + usize::from(read_u16(data, 30));
+		let length = extra_field_end + usize::from(read_u16(data, 32));
+		if data.len() < length {
+			return Err(Error::InvalidArchive("central directory is truncated"));
+		}
+		let (size, raw_size, position) =
+			(read_u32(data, 20), read_u32(data, 24), read_u32(data, 42));
+		if [size, raw_size, position].contains(&u32::MAX) {
+			return Err(Error::Unsupported("ZIP64 archives"));
+		}
+		let flags = read_u16(data, 8);
+		let raw_name = data[46..name_end].to_vec();
+		let extra_field = data[name_end..extra_field_end].to_vec();
+		let raw_comment = data[extra_field_end..length].to_vec();
+		let (name, comment) = match flags & FLAG_UTF8 {
+			0 => (
+				cp437::decode_name(&raw_name, &extra_field),
+				cp437::decode_comment(&raw_comment, &extra_field),
+			),
+			_ => (
+				String::from_utf8_lossy(&raw_name).into_owned(),
+				String::from_utf8_lossy(&raw_comment).into_owned(),
+			),
+		};
+		// Attributes are written back unchanged, permissions are only meaningful for entries
+		// made on Unix.
+		let permissions = match (data[5], read_u32(data, 38) >> 16) {
+			(3, mode) if mode != 0 => Some(mode),
+			_ => None,
+		};
+		entries.push(Entry {
+			comment,
+			crc: read_u32(data, 16),
+			date_time: DateTime::from_le_bytes([data[12], data[13], data[14], data[15]]),
+			external_attributes: read_u32(data, 38),
+			extra_field,
+			flags,
+			internal_attributes: read_u16(data, 36),
+			method: read_u16(data, 10),
+			name,
+			permissions,
+			position: position.into(),
+			raw_comment,
+			raw_name,
+			raw_size: raw_size.into(),
+			size: size.into(),
+			version: read_u16(data, 6),
+			version_made_by: read_u16(data, 4),
+		});
+		data = &data[length..];
+	}
+
+	Ok(entries)
+}
+
+pub(crate) fn read<R: Read + Seek>(reader: &mut R) -> Result<(End, Vec<Entry>), Error> {
+	let start = reader.seek(SeekFrom::End(0))?.saturating_sub(MAX_END_SIZE);
+	reader.seek(SeekFrom::Start(start))?;
+	let mut tail = Vec::new();
+	reader.read_to_end(&mut tail)?;
+	let end = find_end(&tail, start)?;
+	reader.seek(SeekFrom::Start(end.offset))?;
+	let mut data = vec![0; end.size as usize];
+	reader.read_exact(&mut data)?;
+	let entries = parse(&data, end.entries)?;
+	Ok((end, entries))
+}
+
+#[cfg(feature = "tokio")]
+pub(crate) async fn read_async<R>(reader: &mut R) -> Result<(End, Vec<Entry>), Error>
+where
+	R: tokio::io::AsyncRead + tokio::io::AsyncSeek + Unpin,
+{
+	use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+	let start = reader.seek(SeekFrom::End(0)).await?.saturating_sub(MAX_END_SIZE);
+	reader.seek(SeekFrom::Start(start)).await?;
+	let mut tail = Vec::new();
+	reader.read_to_end(&mut tail).await?;
+	let end = find_end(&tail, start)?;
+	reader.seek(SeekFrom::Start(end.offset)).await?;
+	let mut data = vec![0; end.size as usize];
+	reader.read_exact(&mut data).await?;
+	let entries = parse(&data, end.entries)?;
+	Ok((end, entries))
+}
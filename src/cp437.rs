@@ -0,0 +1,105 @@
+use crate::{extra, Error};
+
+const HIGH: [char; 128] = [
+	'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å', 'É', 'æ', 'Æ',
+	'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ', 'á', 'í', 'ó', 'ú', 'ñ', 'Ñ',
+	'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '»', '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕',
+	'╣', '║', '╗', '╝', '╜', '╛', '┐', '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦',
+	'╠', '═', '╬', '╧', '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐',
+	'▀', 'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩', '≡', '±',
+	'≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', '\u{A0}',
+];
+pub(crate) const UNICODE_COMMENT: u16 = 0x6375;
+pub(crate) const UNICODE_PATH: u16 = 0x7075;
+
+pub(crate) fn decode(data: &[u8]) -> String {
+	data.iter()
+		.map(|&byte| match byte {
+			0x00..=0x7F => char::from(byte),
+			_ => HIGH[usize::from(byte - 0x80)],
+		})
+		.collect()
+}
+
+// Unicode extra fields take precedence when they were written for the same raw text.
+pub(crate) fn decode_comment(raw_comment: &[u8], extra_field: &[u8]) -> String {
+	decode_unicode(UNICODE_COMMENT, raw_comment, extra_field).unwrap_or_else(|| decode(raw_comment))
+}
+
+pub(crate) fn decode_name(raw_name: &[u8], extra_field: &[u8]) -> String {
+	decode_unicode(UNICODE_PATH, raw_name, extra_field).unwrap_or_else(|| decode(raw_name))
+}
+
+fn decode_unicode(id: u16, raw: &[u8], extra_field: &[u8]) -> Option<String> {
+	match extra::find(extra_field, id)? {
+		[1, a, b, c, d, text @ ..] if u32::from_le_bytes([*a, *b, *c, *d]) == crc32(raw) => {
+			String::from_utf8(text.to_vec()).ok()
+		}
+		_ => None,
+	}
+}
+
+// Characters outside of CP437 are replaced with underscores, in which case `false` is returned.
+fn encode(text: &str) -> (Vec<u8>, bool) {
+	let mut representable = true;
+	let data = text
+		.chars()
+		.map(|c| match c {
+			'\0'..='\x7F' => c as u8,
+			_ => match HIGH.iter().position(|&high| high == c) {
+				Some(index) => 0x80 + index as u8,
+				None => {
+					representable = false;
+					b'_'
+				}
+			},
+		})
+		.collect();
+	(data, representable)
+}
+
+// Text that CP437 cannot represent is also stored in the Info-ZIP Unicode Path or Comment extra
+// fields. The comment field only belongs to the central directory.
+pub(crate) fn encode_comment(
+	comment: &str,
+	central_extra_field: &mut Vec<u8>,
+) -> Result<Vec<u8>, Error> {
+	let (raw_comment, representable) = encode(comment);
+	if !representable {
+		central_extra_field.extend(unicode_extra_field(UNICODE_COMMENT, &raw_comment, comment)?);
+	}
+	Ok(raw_comment)
+}
+
+pub(crate) fn encode_name(name: &str, extra_field: &mut Vec<u8>) -> Result<Vec<u8>, Error> {
+	let (raw_name, representable) = encode(name);
+	if !representable {
+		extra_field.extend(unicode_extra_field(UNICODE_PATH, &raw_name, name)?);
+	}
+	Ok(raw_name)
+}
+
+fn unicode_extra_field(id: u16, encoded: &[u8], text: &str) -> Result<Vec<u8>, Error> {
+	if text.len() + 5 > u16::MAX.into() {
+		return Err(Error::ExtraFieldTooLong(text.len() + 9));
+	}
+	let mut field = Vec::with_capacity(9 + text.len());
+	field.extend(id.to_le_bytes());
+	field.extend((5 + text.len() as u16).to_le_bytes());
+	field.push(1);
+	field.extend(crc32(encoded).to_le_bytes());
+	field.extend(text.as_bytes());
+	Ok(field)
+}
+
+// Names are short, a bitwise implementation is enough and does not depend on the crc feature.
+fn crc32(data: &[u8]) -> u32 {
+	let mut crc = !0u32;
+	for &byte in data {
+		crc ^= u32::from(byte);
+		for _ in 0..8 {
+			crc = (crc >> 1) ^ (0xEDB88320 & (crc & 1).wrapping_neg());
+		}
+	}
+	!crc
+}
@@ -1,8 +1,141 @@
-#[derive(Default)]
-pub struct DateTime;
+use crate::Error;
+use std::{
+	env,
+	time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+// Fields are ordered so that the derived ordering is chronological.
+#[derive(Clone, Copy, Debug, Default, Eq, Ord, PartialEq, PartialOrd)]
+pub struct DateTime {
+	date: u16,
+	time: u16,
+}
 
 impl DateTime {
+	pub fn new(
+		year: u16,
+		month: u8,
+		day: u8,
+		hour: u8,
+		minute: u8,
+		second: u8,
+	) -> Result<Self, Error> {
+		if !(1980..=2107).contains(&year)
+			|| !(1..=12).contains(&month)
+			|| day == 0
+			|| day > days_in_month(year, month)
+			|| hour > 23
+			|| minute > 59
+			|| second > 59
+		{
+			return Err(Error::InvalidDate);
+		}
+		Ok(Self::encode(year, month, day, hour, minute, second))
+	}
+
+	pub(crate) fn from_le_bytes(bytes: [u8; 4]) -> Self {
+		let [time_low, time_high, date_low, date_high] = bytes;
+		Self {
+			date: u16::from_le_bytes([date_low, date_high]),
+			time: u16::from_le_bytes([time_low, time_high]),
+		}
+	}
+
 	pub fn to_le_bytes(&self) -> [u8; 4] {
-		[0, 0, 0, 0]
+		let [time_low, time_high] = self.time.to_le_bytes();
+		let [date_low, date_high] = self.date.to_le_bytes();
+		[time_low, time_high, date_low, date_high]
+	}
+
+	// Reads SOURCE_DATE_EPOCH, falling back to the earliest representable time.
+	pub(crate) fn source_date_epoch() -> Self {
+		let seconds =
+			env::var("SOURCE_DATE_EPOCH").ok().and_then(|epoch| epoch.trim().parse().ok());
+		let Some(seconds) = seconds else {
+			return Self::encode(1980, 1, 1, 0, 0, 0);
+		};
+		// Times beyond those of `SystemTime` are clamped like the ones past 2107.
+		UNIX_EPOCH
+			.checked_add(Duration::from_secs(seconds))
+			.map_or(Self::encode(2107, 12, 31, 23, 59, 59), Self::from)
+	}
+
+	fn encode(year: u16, month: u8, day: u8, hour: u8, minute: u8, second: u8) -> Self {
+		Self {
+			date: (year - 1980) << 9 | u16::from(month) << 5 | u16::from(day),
+			time: u16::from(hour) << 11 | u16::from(minute) << 5 | u16::from(second / 2),
+		}
+	}
+}
+
+// Times are interpreted as UTC and clamped to the range that MS-DOS timestamps can represent.
+impl From<SystemTime> for DateTime {
+	fn from(time: SystemTime) -> Self {
+		let seconds = time.duration_since(UNIX_EPOCH).map_or(0, |duration| duration.as_secs());
+		let (year, month, day) = civil_from_days(seconds / 86400);
+		if year < 1980 {
+			return Self::encode(1980, 1, 1, 0, 0, 0);
+		}
+		if year > 2107 {
+			return Self::encode(2107, 12, 31, 23, 59, 59);
+		}
+		let seconds = seconds % 86400;
+		Self::encode(
+			year as u16,
+			month,
+			day,
+			(seconds / 3600) as u8,
+			(seconds / 60 % 60) as u8,
+			(seconds % 60) as u8,
+		)
+	}
+}
+
+// Interpreted as UTC, the inverse of the conversion from `SystemTime`.
+impl From<DateTime> for SystemTime {
+	fn from(date_time: DateTime) -> Self {
+		let year = u64::from(date_time.date >> 9) + 1980;
+		let month = (date_time.date >> 5 & 0x0F).clamp(1, 12) as u8;
+		let day = (date_time.date & 0x1F).max(1) as u8;
+		let seconds = u64::from(date_time.time >> 11) * 3600
+			+ u64::from(date_time.time >> 5 & 0x3F) * 60
+			+ u64::from(date_time.time & 0x1F) * 2;
+		UNIX_EPOCH + Duration::from_secs(days_from_civil(year, month, day) * 86400 + seconds)
+	}
+}
+
+fn civil_from_days(days: u64) -> (u64, u8, u8) {
+	let days = days + 719468;
+	let era = days / 146097;
+	let day_of_era = days % 146097;
+	let year_of_era =
+		(day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+	let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+	let month = (5 * day_of_year + 2) / 153;
+	let day = (day_of_year - (153 * month + 2) / 5 + 1) as u8;
+	let month = if month < 10 { month + 3 } else { month - 9 } as u8;
+	let year = year_of_era + era * 400 + u64::from(month <= 2);
+	(year, month, day)
+}
+
+fn days_from_civil(year: u64, month: u8, day: u8) -> u64 {
+	let year = year - u64::from(month <= 2);
+	let era = year / 400;
+	let year_of_era = year % 400;
+	let month = u64::from(month);
+	let day_of_year =
+		(153 * if month > 2 { month - 3 } else { month + 9 } + 2) / 5 + u64::from(day) - 1;
+	let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+	era * 146097 + day_of_era - 719468
+}
+
+fn days_in_month(year: u16, month: u8) -> u8 {
+	match month {
+		2 if year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400)) => {
+			29
+		}
+		2 => 28,
+		4 | 6 | 9 | 11 => 30,
+		_ => 31,
 	}
 }
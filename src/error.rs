@@ -0,0 +1,136 @@
+use std::{
+	error, fmt,
+	io::{self, ErrorKind},
+};
+
+#[derive(Debug)]
+pub enum Error {
+	ChecksumMismatch(String),
+	CommentTooLong(usize),
+	DuplicateName(String),
+	Encoder(io::Error),
+	ExtraFieldTooLong(usize),
+	InvalidArchive(&'static str),
+	InvalidDate,
+	InvalidName(String),
+	Io(io::Error),
+	LimitExceeded(Limit),
+	NameTooLong(usize),
+	NoEntry,
+	Poisoned,
+	ReservedExtraField(u16),
+	UnknownEntry(String),
+	Unsupported(&'static str),
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Limit {
+	Entries,
+	Offset,
+	Size,
+}
+
+impl fmt::Display for Error {
+	fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::ChecksumMismatch(name) => {
+				write!(
+					formatter,
+					"checksum of entry {name:?} does not match its contents"
+				)
+			}
+			Self::CommentTooLong(length) => {
+				write!(
+					formatter,
+					"entry comment is {length} bytes long, at most {} are allowed",
+					u16::MAX
+				)
+			}
+			Self::DuplicateName(name) => {
+				write!(formatter, "an entry named {name:?} already exists")
+			}
+			Self::Encoder(error) => write!(formatter, "encoder failed: {error}"),
+			Self::ExtraFieldTooLong(length) => {
+				write!(
+					formatter,
+					"extra field is {length} bytes long, at most {} are allowed",
+					u16::MAX
+				)
+			}
+			Self::InvalidArchive(reason) => write!(formatter, "invalid archive: {reason}"),
+			Self::InvalidDate => {
+				write!(
+					formatter,
+					"date and time cannot be represented in MS-DOS format"
+				)
+			}
+			Self::InvalidName(name) => write!(formatter, "{name:?} is not a valid entry name"),
+			Self::Io(error) => error.fmt(formatter),
+			Self::LimitExceeded(limit) => {
+				let limit = match limit {
+					Limit::Entries => "number of entries",
+					Limit::Offset => "archive size",
+					Limit::Size => "entry size",
+				};
+				write!(formatter, "{limit} exceeds the limits of the ZIP format")
+			}
+			Self::NameTooLong(length) => {
+				write!(
+					formatter,
+					"entry name is {length} bytes long, at most {} are allowed",
+					u16::MAX
+				)
+			}
+			Self::NoEntry => write!(formatter, "no entry is being written"),
+			Self::Poisoned => {
+				write!(
+					formatter,
+					"archive cannot be written to after a previous write failed"
+				)
+			}
+			Self::ReservedExtraField(id) => {
+				write!(formatter, "extra field {id:#06X} is managed by the writer")
+			}
+			Self::UnknownEntry(name) => write!(formatter, "no entry is named {name:?}"),
+			Self::Unsupported(feature) => write!(formatter, "{feature} are not supported"),
+		}
+	}
+}
+
+impl error::Error for Error {
+	fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+		match self {
+			Self::Encoder(error) | Self::Io(error) => Some(error),
+			_ => None,
+		}
+	}
+}
+
+impl From<io::Error> for Error {
+	fn from(error: io::Error) -> Self {
+		if !error.get_ref().is_some_and(|inner| inner.is::<Self>()) {
+			return Self::Io(error);
+		}
+		match error.into_inner().map(|inner| inner.downcast::<Self>()) {
+			Some(Ok(error)) => *error,
+			_ => unreachable!(),
+		}
+	}
+}
+
+impl From<Error> for io::Error {
+	fn from(error: Error) -> Self {
+		match error {
+			Error::Encoder(error) => Self::new(error.kind(), Error::Encoder(error)),
+			Error::Io(error) => error,
+			Error::ChecksumMismatch(_) | Error::InvalidArchive(_) => {
+				Self::new(ErrorKind::InvalidData, error)
+			}
+			Error::UnknownEntry(_) => Self::new(ErrorKind::NotFound, error),
+			Error::Unsupported(_) => Self::new(ErrorKind::Unsupported, error),
+			Error::LimitExceeded(_) => Self::new(ErrorKind::FileTooLarge, error),
+			Error::Poisoned => Self::other(error),
+			_ => Self::new(ErrorKind::InvalidInput, error),
+		}
+	}
+}
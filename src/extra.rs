@@ -0,0 +1,83 @@
+use crate::Error;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub(crate) const EXTENDED_TIMESTAMP: u16 = 0x5455;
+// 100 ns intervals between 1601-01-01, the origin of Windows times, and the Unix epoch.
+const FILETIME_EPOCH: u128 = 116444736000000000;
+pub(crate) const NTFS: u16 = 0x000A;
+const UNIX_OWNER: u16 = 0x7875;
+// Fields written by the crate itself: ZIP64, NTFS and Unix timestamps, Unicode path and comment,
+// Unix owner and AES encryption.
+const RESERVED: &[u16] = &[0x0001, 0x000A, 0x5455, 0x6375, 0x7075, 0x7875, 0x9901];
+
+pub(crate) fn push(extra_field: &mut Vec<u8>, id: u16, data: &[u8]) -> Result<(), Error> {
+	if RESERVED.contains(&id) {
+		return Err(Error::ReservedExtraField(id));
+	}
+	if data.len() > usize::from(u16::MAX) - 4 {
+		return Err(Error::ExtraFieldTooLong(data.len() + 4));
+	}
+	extra_field.extend(id.to_le_bytes());
+	extra_field.extend((data.len() as u16).to_le_bytes());
+	extra_field.extend(data);
+	Ok(())
+}
+
+pub(crate) fn find(mut extra_field: &[u8], id: u16) -> Option<&[u8]> {
+	while extra_field.len() >= 4 {
+		let size = usize::from(u16::from_le_bytes([extra_field[2], extra_field[3]]));
+		let data = extra_field[4..].get(..size)?;
+		if u16::from_le_bytes([extra_field[0], extra_field[1]]) == id {
+			return Some(data);
+		}
+		extra_field = &extra_field[4 + size..];
+	}
+	None
+}
+
+pub(crate) fn remove(mut extra_field: &[u8], id: u16) -> Vec<u8> {
+	let mut kept = Vec::with_capacity(extra_field.len());
+	while extra_field.len() >= 4 {
+		let size = usize::from(u16::from_le_bytes([extra_field[2], extra_field[3]]));
+		let length = (4 + size).min(extra_field.len());
+		if u16::from_le_bytes([extra_field[0], extra_field[1]]) != id {
+			kept.extend(&extra_field[..length]);
+		}
+		extra_field = &extra_field[length..];
+	}
+	kept.extend(extra_field);
+	kept
+}
+
+// Info-ZIP new Unix field, storing identifiers on as few bytes as possible.
+pub(crate) fn owner(uid: u32, gid: u32) -> Vec<u8> {
+	let mut data = vec![1];
+	for id in [uid, gid] {
+		let size = (4 - id.leading_zeros() as usize / 8).max(1);
+		data.push(size as u8);
+		data.extend(&id.to_le_bytes()[..size]);
+	}
+	let mut field = Vec::with_capacity(4 + data.len());
+	field.extend(UNIX_OWNER.to_le_bytes());
+	field.extend((data.len() as u16).to_le_bytes());
+	field.extend(data);
+	field
+}
+
+// Times are given in the order of the field: modification, access and creation.
+pub(crate) fn ntfs(times: [SystemTime; 3]) -> Vec<u8> {
+	let mut field = Vec::with_capacity(36);
+	field.extend(NTFS.to_le_bytes());
+	field.extend(32u16.to_le_bytes());
+	field.extend([0x00, 0x00, 0x00, 0x00]);
+	field.extend(0x0001u16.to_le_bytes());
+	field.extend(24u16.to_le_bytes());
+	for time in times {
+		let ticks = match time.duration_since(UNIX_EPOCH) {
+			Ok(duration) => FILETIME_EPOCH + duration.as_nanos() / 100,
+			Err(error) => FILETIME_EPOCH.saturating_sub(error.duration().as_nanos() / 100),
+		};
+		field.extend(u64::try_from(ticks).unwrap_or(u64::MAX).to_le_bytes());
+	}
+	field
+}
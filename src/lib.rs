@@ -1,64 +1,197 @@
+#[cfg(feature = "aes")]
+use aes::AesWriter;
 #[cfg(feature = "crc")]
 use crc32fast::Hasher;
 #[cfg(feature = "deflate")]
 use flate2::{self, write::DeflateEncoder};
+use name::{EntryName, Names};
 use std::{
-	io::{self, Error, ErrorKind, Write},
+	fs::File,
+	io::{self, Read, Seek, SeekFrom, Write},
 	mem::replace,
+	path::Path,
+	time::SystemTime,
 };
+use walk::Kind;
 
+#[cfg(feature = "aes")]
+mod aes;
+mod central;
+mod cp437;
 mod date;
+mod error;
+mod extra;
+mod name;
+mod options;
+pub mod read;
 #[cfg(test)]
 mod test;
 #[cfg(feature = "tokio")]
 pub mod tokio;
+mod transform;
+mod verify;
+mod walk;
 
+#[cfg(feature = "aes")]
+pub use aes::{AesStrength, AesVersion};
 pub use date::DateTime;
+pub use error::{Error, Limit};
+pub use name::{Duplicates, NamePolicy};
+pub use options::EntryOptions;
+pub use transform::Action;
+pub use verify::{verify, Problem, Report};
+pub use walk::Filter;
 
 const CENTRAL_DIRECTORY_HEADER: &[u8] = &[0x50, 0x4B, 0x01, 0x02];
 const END_CENTRAL_DIRECTORY: &[u8] = &[0x50, 0x4B, 0x05, 0x06];
+const DATA_DESCRIPTOR: &[u8] = &[0x50, 0x4B, 0x07, 0x08];
+const DIRECTORY: u32 = 0o040000;
+const FILE_TYPE: u32 = 0o170000;
+const FLAGS: u16 = 0b00001000_00001000;
+const FLAG_DESCRIPTOR: u16 = 0b00000000_00001000;
+const FLAG_ENCRYPTED: u16 = 0b00000000_00000001;
+const FLAG_UTF8: u16 = 0b00001000_00000000;
 const LOCAL_HEADER: &[u8] = &[0x50, 0x4B, 0x03, 0x04];
-const PLATFORM: &[u8] = &[0x00, 0x00];
-const VERSION: &[u8] = &[0x14, 0x00];
+const PLATFORM: u16 = 0x003F;
+const PLATFORM_UNIX: u16 = 0x033F;
+const REGULAR_FILE: u32 = 0o100000;
+const SYMLINK: u32 = 0o120000;
 
+#[derive(Clone)]
 pub enum Compression {
 	#[cfg(feature = "deflate")]
 	Deflate,
 	None,
 }
 
+impl Default for Compression {
+	fn default() -> Self {
+		#[cfg(feature = "deflate")]
+		return Self::Deflate;
+		#[cfg(not(feature = "deflate"))]
+		return Self::None;
+	}
+}
+
 impl Compression {
-	fn to_le_bytes(&self) -> [u8; 2] {
+	fn method(&self) -> u16 {
 		match self {
 			#[cfg(feature = "deflate")]
-			Self::Deflate => [0x08, 0x00],
-			Self::None => [0x00, 0x00],
+			Self::Deflate => 8,
+			Self::None => 0,
 		}
 	}
 }
 
-struct Entry {
-	pub compression: Compression,
-	pub crc: u32,
-	pub date_time: DateTime,
-	pub name: String,
-	pub position: u64,
-	pub raw_size: u64,
+#[derive(Clone, Default)]
+pub enum Encryption {
+	#[cfg(feature = "aes")]
+	Aes {
+		password: String,
+		strength: AesStrength,
+		version: AesVersion,
+	},
+	#[default]
+	None,
+}
+
+#[derive(Clone)]
+pub struct Entry {
+	comment: String,
+	crc: u32,
+	date_time: DateTime,
+	external_attributes: u32,
+	extra_field: Vec<u8>,
+	flags: u16,
+	internal_attributes: u16,
+	method: u16,
+	name: String,
+	permissions: Option<u32>,
+	position: u64,
+	raw_comment: Vec<u8>,
+	raw_name: Vec<u8>,
+	raw_size: u64,
+	size: u64,
+	version: u16,
+	version_made_by: u16,
+}
+
+impl Entry {
+	pub fn comment(&self) -> &str {
+		&self.comment
+	}
+
+	pub fn compressed_size(&self) -> u64 {
+		self.size
+	}
+
+	pub fn crc(&self) -> u32 {
+		self.crc
+	}
+
+	pub fn date_time(&self) -> &DateTime {
+		&self.date_time
+	}
+
+	pub fn method(&self) -> u16 {
+		self.method
+	}
+
+	pub fn name(&self) -> &str {
+		&self.name
+	}
+
+	// Name as written in the headers.
+	pub fn name_bytes(&self) -> &[u8] {
+		&self.raw_name
+	}
+
+	pub fn permissions(&self) -> Option<u32> {
+		self.permissions
+	}
+
+	pub fn offset(&self) -> u64 {
+		self.position
+	}
+
+	pub fn uncompressed_size(&self) -> u64 {
+		self.raw_size
+	}
+
+	pub fn version_needed(&self) -> u16 {
+		self.version
+	}
+}
+
+pub struct Summary {
+	pub central_directory_offset: u64,
+	pub entries: Vec<Entry>,
 	pub size: u64,
 }
 
 enum Writer<W: Write> {
+	#[cfg(feature = "aes")]
+	Aes(AesWriter<W>),
+	#[cfg(all(feature = "aes", feature = "deflate"))]
+	AesDeflate(DeflateEncoder<AesWriter<W>>),
 	#[cfg(feature = "deflate")]
 	Deflate(DeflateEncoder<W>),
 	Raw(W),
-	None,
+	Poisoned,
 }
 
 pub struct Zip<W: Write> {
 	#[cfg(feature = "crc")]
 	crc: Hasher,
+	// Comment of an archive that entries are appended to.
+	comment: Vec<u8>,
 	cursor: u64,
+	descriptor_signature: bool,
 	entries: Vec<Entry>,
+	entry: Option<Entry>,
+	epoch: Option<DateTime>,
+	name_policy: NamePolicy,
+	names: Names,
 	writer: Writer<W>,
 }
 
@@ -67,81 +200,219 @@ impl<W: Write> Zip<W> {
 		Self {
 			#[cfg(feature = "crc")]
 			crc: Hasher::new(),
+			comment: Vec::new(),
+			descriptor_signature: true,
 			entries: Vec::new(),
+			entry: None,
+			epoch: None,
+			name_policy: NamePolicy::default(),
+			names: Names::default(),
 			cursor: 0,
 			writer: Writer::Raw(writer),
 		}
 	}
 
+	// The signature of data descriptors is optional, but most readers expect it.
+	pub fn descriptor_signature(mut self, signature: bool) -> Self {
+		self.descriptor_signature = signature;
+		self
+	}
+
+	// Duplicate names are detected among the entries created by this writer. Comparisons can
+	// ignore case, as extracting to Windows or macOS file systems would.
+	pub fn duplicates(mut self, duplicates: Duplicates, case_insensitive: bool) -> Self {
+		self.names = Names::new(duplicates, case_insensitive);
+		for entry in &self.entries {
+			self.names.insert(&entry.name);
+		}
+		self
+	}
+
+	pub fn name_policy(mut self, policy: NamePolicy) -> Self {
+		self.name_policy = policy;
+		self
+	}
+
+	// Timestamps are clamped to SOURCE_DATE_EPOCH, or to 1980-01-01 when it is not set.
+	pub fn reproducible(self) -> Self {
+		self.reproducible_at(DateTime::source_date_epoch())
+	}
+
+	// Clamps timestamps to `epoch` and normalizes permissions, so that archiving the same content
+	// always yields the same bytes. Directory walks are always sorted.
+	pub fn reproducible_at(mut self, epoch: DateTime) -> Self {
+		self.epoch = Some(epoch);
+		self
+	}
+
 	pub fn create_entry<T: Into<String>>(
 		&mut self,
 		name: T,
-		compression: Compression,
-		date_time: DateTime,
-	) -> io::Result<()> {
-		let name = name.into();
-		if name.len() > u16::MAX.into() {
-			return Err(Error::new(ErrorKind::InvalidInput, ""));
+		options: EntryOptions,
+	) -> Result<EntryWriter<'_, W>, Error> {
+		self.start_entry(EntryName::Text(name.into()), options)?;
+		Ok(EntryWriter { zip: self })
+	}
+
+	// Names are written as given, without conversion. When `utf8` is false, readers interpret
+	// them as CP437 or in the encoding of their system.
+	pub fn create_entry_raw<T: Into<Vec<u8>>>(
+		&mut self,
+		name: T,
+		utf8: bool,
+		options: EntryOptions,
+	) -> Result<EntryWriter<'_, W>, Error> {
+		self.start_entry(EntryName::Raw(name.into(), utf8), options)?;
+		Ok(EntryWriter { zip: self })
+	}
+
+	pub fn add_file<P: AsRef<Path>, T: Into<String>>(
+		&mut self,
+		path: P,
+		name: T,
+		options: EntryOptions,
+	) -> Result<Entry, Error> {
+		let mut file = File::open(path)?;
+		let options = options.metadata(&file.metadata()?);
+		let mut entry = self.create_entry(name, options)?;
+		io::copy(&mut file, &mut entry)?;
+		entry.finish()
+	}
+
+	pub fn add_dir_all<P: AsRef<Path>>(
+		&mut self,
+		root: P,
+		prefix: &str,
+		filter: &Filter,
+		options: EntryOptions,
+	) -> Result<(), Error> {
+		for item in walk::walk(root.as_ref(), filter)? {
+			let name = walk::join(prefix, &item.name);
+			match item.kind {
+				Kind::Directory => {
+					let options = options
+						.clone()
+						.compression(Compression::None)
+						.encryption(Encryption::None)
+						.metadata(&item.metadata);
+					self.create_entry(name + "/", options)?.finish()?;
+				}
+				Kind::File => {
+					self.add_file(&item.path, name, options.clone())?;
+				}
+				Kind::Symlink(target) => {
+					name::check_target(self.name_policy, &name, &target)?;
+					let options = options
+						.clone()
+						.compression(Compression::None)
+						.encryption(Encryption::None)
+						.metadata(&item.metadata);
+					let mut entry = self.create_entry(name, options)?;
+					entry.write_all(target.as_bytes())?;
+					entry.finish()?;
+				}
+			}
 		}
+
+		Ok(())
+	}
+
+	fn start_entry(&mut self, name: EntryName, options: EntryOptions) -> Result<(), Error> {
+		#[cfg(feature = "deflate")]
+		let level = options.level.map_or_else(flate2::Compression::default, flate2::Compression::new);
+		let (mut entry, local_extra_field) =
+			prepare_entry(&self.names, self.name_policy, self.epoch, name, &options)?;
 		let mut writer = self.commit_previous()?;
+		self.names.insert(&entry.name);
+		if self.cursor > u32::MAX.into() {
+			return Err(Error::LimitExceeded(Limit::Offset));
+		}
 		writer.write_all(LOCAL_HEADER)?;
-		writer.write_all(VERSION)?;
-		writer.write_all(&[0b00001000, 0b00001000])?;
-		writer.write_all(&compression.to_le_bytes())?;
-		writer.write_all(&date_time.to_le_bytes())?;
+		writer.write_all(&entry.version.to_le_bytes())?;
+		writer.write_all(&entry.flags.to_le_bytes())?;
+		writer.write_all(&entry.method.to_le_bytes())?;
+		writer.write_all(&entry.date_time.to_le_bytes())?;
 		writer.write_all(&[0x00, 0x00, 0x00, 0x00])?;
 		writer.write_all(&[0x00, 0x00, 0x00, 0x00])?;
 		writer.write_all(&[0x00, 0x00, 0x00, 0x00])?;
-		writer.write_all(&(name.len() as u16).to_le_bytes())?;
-		writer.write_all(&[0x00, 0x00])?;
-		writer.write_all(name.as_bytes())?;
+		writer.write_all(&(entry.raw_name.len() as u16).to_le_bytes())?;
+		writer.write_all(&(local_extra_field.len() as u16).to_le_bytes())?;
+		writer.write_all(&entry.raw_name)?;
+		writer.write_all(&local_extra_field)?;
 		_ = replace(
 			&mut self.writer,
-			match compression {
+			match (options.compression, options.encryption) {
+				#[cfg(all(feature = "aes", feature = "deflate"))]
+				(
+					Compression::Deflate,
+					Encryption::Aes {
+						password,
+						strength,
+						version,
+					},
+				) => Writer::AesDeflate(DeflateEncoder::new(
+					AesWriter::new(writer, &password, &strength, version)
+						.map_err(Error::Encoder)?,
+					level,
+				)),
+				#[cfg(feature = "aes")]
+				(
+					Compression::None,
+					Encryption::Aes {
+						password,
+						strength,
+						version,
+					},
+				) => Writer::Aes(
+					AesWriter::new(writer, &password, &strength, version)
+						.map_err(Error::Encoder)?,
+				),
 				#[cfg(feature = "deflate")]
-				Compression::Deflate => {
-					Writer::Deflate(DeflateEncoder::new(writer, flate2::Compression::default()))
-				}
-				Compression::None => Writer::Raw(writer),
+				(Compression::Deflate, Encryption::None) => Writer::Deflate(DeflateEncoder::new(writer, level)),
+				(Compression::None, Encryption::None) => Writer::Raw(writer),
 			},
 		);
-		let position = self.cursor;
-		self.cursor += 30 + name.len() as u64;
-		self.entries.push(Entry {
-			compression,
-			crc: 0,
-			date_time,
-			name,
-			position,
-			raw_size: 0,
-			size: self.cursor,
-		});
+		entry.position = self.cursor;
+		self.cursor += 30 + entry.raw_name.len() as u64 + local_extra_field.len() as u64;
+		entry.size = self.cursor;
+		self.entry = Some(entry);
 
 		Ok(())
 	}
 
-	pub fn finish(mut self) -> io::Result<()> {
+	pub fn finish(mut self) -> Result<(W, Summary), Error> {
 		let mut writer = self.commit_previous()?;
+		if self.entries.len() > u16::MAX.into() {
+			return Err(Error::LimitExceeded(Limit::Entries));
+		}
 		let position = self.cursor;
 		for entry in &self.entries {
 			writer.write_all(CENTRAL_DIRECTORY_HEADER)?;
-			writer.write_all(PLATFORM)?;
-			writer.write_all(VERSION)?;
-			writer.write_all(&[0b00001000, 0b00001000])?;
-			writer.write_all(&entry.compression.to_le_bytes())?;
+			writer.write_all(&entry.version_made_by.to_le_bytes())?;
+			writer.write_all(&entry.version.to_le_bytes())?;
+			writer.write_all(&entry.flags.to_le_bytes())?;
+			writer.write_all(&entry.method.to_le_bytes())?;
 			writer.write_all(&entry.date_time.to_le_bytes())?;
 			writer.write_all(&entry.crc.to_le_bytes())?;
 			writer.write_all(&(entry.size as u32).to_le_bytes())?;
 			writer.write_all(&(entry.raw_size as u32).to_le_bytes())?;
-			writer.write_all(&(entry.name.len() as u16).to_le_bytes())?;
-			writer.write_all(&[0x00, 0x00])?;
-			writer.write_all(&[0x00, 0x00])?;
+			writer.write_all(&(entry.raw_name.len() as u16).to_le_bytes())?;
+			writer.write_all(&(entry.extra_field.len() as u16).to_le_bytes())?;
+			writer.write_all(&(entry.raw_comment.len() as u16).to_le_bytes())?;
 			writer.write_all(&[0x00, 0x00])?;
-			writer.write_all(&[0x00, 0x00])?;
-			writer.write_all(&[0x00, 0x00, 0x00, 0x00])?;
+			writer.write_all(&entry.internal_attributes.to_le_bytes())?;
+			writer.write_all(&entry.external_attributes.to_le_bytes())?;
 			writer.write_all(&(entry.position as u32).to_le_bytes())?;
-			writer.write_all(entry.name.as_bytes())?;
-			self.cursor += 46 + entry.name.len() as u64;
+			writer.write_all(&entry.raw_name)?;
+			writer.write_all(&entry.extra_field)?;
+			writer.write_all(&entry.raw_comment)?;
+			self.cursor += 46
+				+ entry.raw_name.len() as u64
+				+ entry.extra_field.len() as u64
+				+ entry.raw_comment.len() as u64;
+		}
+		if self.cursor > u32::MAX.into() {
+			return Err(Error::LimitExceeded(Limit::Offset));
 		}
 		let number_entries = self.entries.len() as u16;
 		let size = (self.cursor - position) as u32;
@@ -152,19 +423,47 @@ impl<W: Write> Zip<W> {
 		writer.write_all(&number_entries.to_le_bytes())?;
 		writer.write_all(&size.to_le_bytes())?;
 		writer.write_all(&(position as u32).to_le_bytes())?;
-		writer.write_all(&[0x00, 0x00])?;
+		writer.write_all(&(self.comment.len() as u16).to_le_bytes())?;
+		writer.write_all(&self.comment)?;
+		self.cursor += 22 + self.comment.len() as u64;
 
-		Ok(())
+		Ok((
+			writer,
+			Summary {
+				central_directory_offset: position,
+				entries: self.entries,
+				size: self.cursor,
+			},
+		))
 	}
 
-	fn commit_previous(&mut self) -> io::Result<W> {
-		let writer = replace(&mut self.writer, Writer::None);
-		let Some(entry) = &mut self.entries.last_mut() else {
+	pub fn finish_entry(&mut self) -> Result<&Entry, Error> {
+		if let Writer::Poisoned = self.writer {
+			return Err(Error::Poisoned);
+		}
+		if self.entry.is_none() {
+			return Err(Error::NoEntry);
+		}
+		self.writer = Writer::Raw(self.commit_previous()?);
+		Ok(&self.entries[self.entries.len() - 1])
+	}
+
+	pub fn entries(&self) -> &[Entry] {
+		&self.entries
+	}
+
+	fn commit_previous(&mut self) -> Result<W, Error> {
+		let writer = replace(&mut self.writer, Writer::Poisoned);
+		let Some(mut entry) = self.entry.take() else {
 			return Ok(match writer {
+				#[cfg(feature = "aes")]
+				Writer::Aes(_) => return Err(Error::Poisoned),
+				#[cfg(all(feature = "aes", feature = "deflate"))]
+				Writer::AesDeflate(_) => return Err(Error::Poisoned),
 				#[cfg(feature = "deflate")]
-				Writer::Deflate(encoder) => encoder.finish()?,
+				Writer::Deflate(encoder) => encoder.finish().map_err(Error::Encoder)?,
 				Writer::Raw(writer) => writer,
-				Writer::None => unreachable!(),
+				Writer::Poisoned => return Err(Error::Poisoned),
 			});
 		};
 		#[cfg(feature = "crc")]
@@ -175,45 +474,255 @@ impl<W: Write> Zip<W> {
 		let start = entry.size;
 		entry.raw_size = self.cursor - start;
 		let (mut writer, size) = match writer {
+			#[cfg(feature = "aes")]
+			Writer::Aes(mut writer) => {
+				writer.flush().map_err(Error::Encoder)?;
+				if writer.hides_crc() {
+					entry.crc = 0;
+				}
+				let (mut writer, code, size) = writer.into_parts();
+				writer.write_all(&code)?;
+				(writer, size)
+			}
+			#[cfg(all(feature = "aes", feature = "deflate"))]
+			Writer::AesDeflate(encoder) => {
+				let mut writer = encoder.finish().map_err(Error::Encoder)?;
+				writer.flush().map_err(Error::Encoder)?;
+				if writer.hides_crc() {
+					entry.crc = 0;
+				}
+				let (mut writer, code, size) = writer.into_parts();
+				writer.write_all(&code)?;
+				(writer, size)
+			}
 			#[cfg(feature = "deflate")]
 			Writer::Deflate(mut encoder) => {
-				encoder.flush()?;
+				encoder.flush().map_err(Error::Encoder)?;
 				let size = encoder.total_out() + 2;
-				(encoder.finish()?, size)
+				(encoder.finish().map_err(Error::Encoder)?, size)
 			}
 			Writer::Raw(writer) => (writer, entry.raw_size),
-			Writer::None => unreachable!(),
+			Writer::Poisoned => return Err(Error::Poisoned),
 		};
 		entry.size = size;
+		if entry.size > u32::MAX.into() || entry.raw_size > u32::MAX.into() {
+			return Err(Error::LimitExceeded(Limit::Size));
+		}
+		if self.descriptor_signature {
+			writer.write_all(DATA_DESCRIPTOR)?;
+		}
 		writer.write_all(&entry.crc.to_le_bytes())?;
 		writer.write_all(&(entry.size as u32).to_le_bytes())?;
 		writer.write_all(&(entry.raw_size as u32).to_le_bytes())?;
 		self.cursor = start + entry.size + 12;
+		if self.descriptor_signature {
+			self.cursor += 4;
+		}
+		self.entries.push(entry);
 
 		Ok(writer)
 	}
 }
 
-impl<W: Write> Write for Zip<W> {
+// Entry about to be written and its local extra field, prepared the same way by the synchronous
+// and asynchronous writers. Its position and size are left to the writer.
+fn prepare_entry(
+	names: &Names,
+	policy: NamePolicy,
+	epoch: Option<DateTime>,
+	name: EntryName,
+	options: &EntryOptions,
+) -> Result<(Entry, Vec<u8>), Error> {
+	let EntryOptions {
+		central_extra_fields,
+		comment,
+		compression,
+		cp437,
+		mut date_time,
+		local_extra_fields,
+		ntfs,
+		ntfs_times,
+		owner,
+		mut permissions,
+		..
+	} = options;
+	if let Some(epoch) = epoch {
+		date_time = date_time.min(epoch);
+		permissions = permissions.map(normalize_permissions);
+	}
+	let (name, raw_name) = names.resolve(policy, name)?;
+	let (mut flags, mut extra_field, mut central_extra_field) = (FLAGS, Vec::new(), Vec::new());
+	#[allow(unused_mut)]
+	let mut method = compression.method();
+	#[cfg(feature = "aes")]
+	if let Encryption::Aes {
+		strength, version, ..
+	} = &options.encryption
+	{
+		flags |= FLAG_ENCRYPTED;
+		method = aes::METHOD;
+		extra_field.extend(aes::extra_field(strength, version, compression));
+	}
+	// Precise times would defeat the clamping of reproducible archives.
+	if *ntfs && epoch.is_none() {
+		let modified = SystemTime::from(date_time);
+		extra_field.extend(extra::ntfs(ntfs_times.unwrap_or([modified; 3])));
+	}
+	if let Some((uid, gid)) = *owner {
+		extra_field.extend(extra::owner(uid, gid));
+	}
+	let utf8 = raw_name.as_ref().map_or(!*cp437, |(_, utf8)| *utf8);
+	if !utf8 {
+		flags &= !FLAG_UTF8;
+	}
+	let raw_name = match raw_name {
+		Some((raw_name, _)) => raw_name,
+		None if *cp437 => cp437::encode_name(&name, &mut extra_field)?,
+		None => name.as_bytes().to_vec(),
+	};
+	let raw_comment = match utf8 {
+		true => comment.as_bytes().to_vec(),
+		false => cp437::encode_comment(comment, &mut central_extra_field)?,
+	};
+	if raw_name.len() > u16::MAX.into() {
+		return Err(Error::NameTooLong(raw_name.len()));
+	}
+	if raw_comment.len() > u16::MAX.into() {
+		return Err(Error::CommentTooLong(raw_comment.len()));
+	}
+	// Fields added so far belong to both headers, apart from the Unicode comment.
+	let mut local_extra_field = extra_field.clone();
+	let mut central_extra_field = [extra_field, central_extra_field].concat();
+	for (id, data) in local_extra_fields {
+		extra::push(&mut local_extra_field, *id, data)?;
+	}
+	for (id, data) in central_extra_fields {
+		extra::push(&mut central_extra_field, *id, data)?;
+	}
+	for extra_field in [&local_extra_field, &central_extra_field] {
+		if extra_field.len() > u16::MAX.into() {
+			return Err(Error::ExtraFieldTooLong(extra_field.len()));
+		}
+	}
+	let version = version_needed(method, &name);
+	let permissions = permissions.map(|mode| match mode & FILE_TYPE {
+		0 => mode | REGULAR_FILE,
+		_ => mode,
+	});
+	let (version_made_by, external_attributes) = attributes(permissions);
+	let entry = Entry {
+		comment: comment.clone(),
+		crc: 0,
+		date_time,
+		external_attributes,
+		extra_field: central_extra_field,
+		flags,
+		internal_attributes: 0,
+		method,
+		name,
+		permissions,
+		position: 0,
+		raw_comment,
+		raw_name,
+		raw_size: 0,
+		size: 0,
+		version,
+		version_made_by,
+	};
+
+	Ok((entry, local_extra_field))
+}
+
+// Version of the specification needed to extract an entry, as a major and minor digit.
+fn version_needed(method: u16, name: &str) -> u16 {
+	match method {
+		#[cfg(feature = "aes")]
+		aes::METHOD => 51,
+		8 => 20,
+		_ if name.ends_with('/') => 20,
+		_ => 10,
+	}
+}
+
+// Version made by and external attributes, which hold the mode of entries made on Unix.
+fn attributes(permissions: Option<u32>) -> (u16, u32) {
+	match permissions {
+		Some(mode) => (PLATFORM_UNIX, mode << 16),
+		None => (PLATFORM, 0),
+	}
+}
+
+// Keeps the file type and whether the entry is executable.
+fn normalize_permissions(mode: u32) -> u32 {
+	match (mode & FILE_TYPE, mode & 0o111) {
+		(DIRECTORY, _) => DIRECTORY | 0o755,
+		(SYMLINK, _) => SYMLINK | 0o777,
+		(_, 0) => mode & FILE_TYPE | 0o644,
+		_ => mode & FILE_TYPE | 0o755,
+	}
+}
+
+impl<W: Read + Write + Seek> Zip<W> {
+	// Entries are added after those of an existing archive, writing over its central directory.
+	// The archive comment is kept, so the new archive is never shorter than the old one.
+	pub fn append(mut writer: W) -> Result<Self, Error> {
+		let (end, entries) = central::read(&mut writer)?;
+		writer.seek(SeekFrom::Start(end.offset))?;
+		Ok(Self {
+			comment: end.comment,
+			cursor: end.offset,
+			entries,
+			..Self::new(writer)
+		})
+	}
+}
+
+pub struct EntryWriter<'a, W: Write> {
+	zip: &'a mut Zip<W>,
+}
+
+impl<W: Write> EntryWriter<'_, W> {
+	pub fn finish(self) -> Result<Entry, Error> {
+		self.zip.finish_entry().cloned()
+	}
+}
+
+impl<W: Write> Write for EntryWriter<'_, W> {
 	fn write(&mut self, data: &[u8]) -> io::Result<usize> {
-		let size = match &mut self.writer {
+		let size = match &mut self.zip.writer {
+			#[cfg(feature = "aes")]
+			Writer::Aes(writer) => writer.write(data),
+			#[cfg(all(feature = "aes", feature = "deflate"))]
+			Writer::AesDeflate(writer) => writer.write(data),
 			#[cfg(feature = "deflate")]
 			Writer::Deflate(writer) => writer.write(data),
 			Writer::Raw(writer) => writer.write(data),
-			Writer::None => unreachable!(),
+			Writer::Poisoned => Err(Error::Poisoned.into()),
 		}?;
 		#[cfg(feature = "crc")]
-		self.crc.update(data);
-		self.cursor += size as u64;
+		self.zip.crc.update(&data[..size]);
+		self.zip.cursor += size as u64;
 		Ok(size)
 	}
 
 	fn flush(&mut self) -> io::Result<()> {
-		match &mut self.writer {
+		match &mut self.zip.writer {
+			#[cfg(feature = "aes")]
+			Writer::Aes(writer) => writer.flush(),
+			#[cfg(all(feature = "aes", feature = "deflate"))]
+			Writer::AesDeflate(writer) => writer.flush(),
 			#[cfg(feature = "deflate")]
 			Writer::Deflate(writer) => writer.flush(),
 			Writer::Raw(writer) => writer.flush(),
-			Writer::None => unreachable!(),
+			Writer::Poisoned => Err(Error::Poisoned.into()),
+		}
+	}
+}
+
+impl<W: Write> Drop for EntryWriter<'_, W> {
+	fn drop(&mut self) {
+		if self.zip.entry.is_some() {
+			_ = self.zip.finish_entry();
 		}
 	}
 }
@@ -0,0 +1,177 @@
+use std::{
+	env,
+	fs::File,
+	io::{self, BufWriter, Write},
+	path::{Component, Path},
+	process::ExitCode,
+	time::SystemTime,
+};
+use zip_writer::{Compression, Duplicates, EntryOptions, Error, Filter, Zip};
+
+const USAGE: &str = "\
+Usage: zip-writer [OPTIONS] [PATH]...
+
+Writes a ZIP archive containing the given files and directories. A path of - or no path at all
+stores standard input as a single entry.
+
+Options:
+  -o, --output <FILE>     Write the archive to FILE instead of standard output
+  -l, --level <LEVEL>     Compression level, from 0 to 9
+  -m, --method <METHOD>   Compression method, deflate or store
+  -n, --name <NAME>       Entry name used for standard input [default: -]
+  -h, --help              Print this message";
+
+struct Arguments {
+	name: String,
+	options: EntryOptions,
+	output: Option<String>,
+	paths: Vec<String>,
+}
+
+fn value(arguments: &mut impl Iterator<Item = String>, option: &str) -> Result<String, String> {
+	arguments.next().ok_or_else(|| format!("option {option} requires a value"))
+}
+
+fn parse(mut arguments: impl Iterator<Item = String>) -> Result<Option<Arguments>, String> {
+	let mut parsed = Arguments {
+		name: "-".to_string(),
+		options: EntryOptions::new(),
+		output: None,
+		paths: Vec::new(),
+	};
+	while let Some(argument) = arguments.next() {
+		match argument.as_str() {
+			"-h" | "--help" => return Ok(None),
+			"-l" | "--level" => {
+				let level = value(&mut arguments, &argument)?;
+				let level = level.parse().map_err(|_| format!("invalid level {level:?}"))?;
+				parsed.options = parsed.options.level(level);
+			}
+			"-m" | "--method" => {
+				let compression = match value(&mut arguments, &argument)?.as_str() {
+					#[cfg(feature = "deflate")]
+					"deflate" => Compression::Deflate,
+					"store" => Compression::None,
+					method => return Err(format!("unsupported method {method:?}")),
+				};
+				parsed.options = parsed.options.compression(compression);
+			}
+			"-n" | "--name" => parsed.name = value(&mut arguments, &argument)?,
+			"-o" | "--output" => parsed.output = Some(value(&mut arguments, &argument)?),
+			"--" => parsed.paths.extend(arguments.by_ref()),
+			option if option.starts_with('-') && option != "-" => {
+				return Err(format!("unknown option {option}"));
+			}
+			_ => parsed.paths.push(argument),
+		}
+	}
+
+	if parsed.paths.is_empty() {
+		parsed.paths.push("-".to_string());
+	}
+
+	Ok(Some(parsed))
+}
+
+// Only the normal components of a path are kept, so that entries never escape the directory the
+// archive is extracted to.
+fn archive_name(path: &Path) -> Result<String, Error> {
+	let mut names = Vec::new();
+	for component in path.components() {
+		if let Component::Normal(name) = component {
+			let name = name.to_str().ok_or_else(|| Error::InvalidName(path.display().to_string()));
+			names.push(name?);
+		}
+	}
+	Ok(names.join("/"))
+}
+
+fn write<W: Write>(writer: W, arguments: Arguments) -> Result<W, Error> {
+	let mut zip = Zip::new(writer).duplicates(Duplicates::Reject, false);
+	for path in &arguments.paths {
+		if path == "-" {
+			let options = arguments.options.clone().date_time(SystemTime::now().into());
+			let mut entry = zip.create_entry(arguments.name.clone(), options)?;
+			io::copy(&mut io::stdin().lock(), &mut entry)?;
+			entry.finish()?;
+			continue;
+		}
+		let path = Path::new(path);
+		let name = archive_name(path)?;
+		if path.is_dir() {
+			zip.add_dir_all(path, &name, &Filter::new(), arguments.options.clone())?;
+		} else {
+			zip.add_file(path, name, arguments.options.clone())?;
+		}
+	}
+	let (writer, _) = zip.finish()?;
+	Ok(writer)
+}
+
+fn run(arguments: Arguments) -> Result<(), Error> {
+	match arguments.output.as_deref() {
+		None | Some("-") => write(BufWriter::new(io::stdout().lock()), arguments)?.flush()?,
+		Some(output) => write(BufWriter::new(File::create(output)?), arguments)?.flush()?,
+	}
+	Ok(())
+}
+
+fn main() -> ExitCode {
+	let arguments = match parse(env::args().skip(1)) {
+		Ok(Some(arguments)) => arguments,
+		Ok(None) => {
+			println!("{USAGE}");
+			return ExitCode::SUCCESS;
+		}
+		Err(error) => {
+			eprintln!("zip-writer: {error}\n\n{USAGE}");
+			return ExitCode::from(2);
+		}
+	};
+	match run(arguments) {
+		Ok(()) => ExitCode::SUCCESS,
+		Err(error) => {
+			eprintln!("zip-writer: {error}");
+			ExitCode::FAILURE
+		}
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	fn parse_all(arguments: &[&str]) -> Result<Option<Arguments>, String> {
+		parse(arguments.iter().map(|argument| argument.to_string()))
+	}
+
+	#[test]
+	fn arguments() {
+		let arguments = parse_all(&[]).unwrap().unwrap();
+		assert_eq!(arguments.name, "-");
+		assert_eq!(arguments.output, None);
+		assert_eq!(arguments.paths, ["-"]);
+
+		let arguments = parse_all(&["-o", "out.zip", "--name", "in.txt", "a", "-", "--", "-b"])
+			.unwrap()
+			.unwrap();
+		assert_eq!(arguments.name, "in.txt");
+		assert_eq!(arguments.output.as_deref(), Some("out.zip"));
+		assert_eq!(arguments.paths, ["a", "-", "-b"]);
+
+		assert!(parse_all(&["a", "--help"]).unwrap().is_none());
+		assert!(parse_all(&["-l", "9", "-m", "store"]).is_ok());
+		assert!(parse_all(&["-l", "x"]).is_err());
+		assert!(parse_all(&["-m", "bzip2"]).is_err());
+		assert!(parse_all(&["-o"]).is_err());
+		assert!(parse_all(&["-x"]).is_err());
+	}
+
+	#[test]
+	fn names() {
+		assert_eq!(archive_name(Path::new("a/b.txt")).unwrap(), "a/b.txt");
+		assert_eq!(archive_name(Path::new("/a/./b/")).unwrap(), "a/b");
+		assert_eq!(archive_name(Path::new("../../a")).unwrap(), "a");
+		assert_eq!(archive_name(Path::new("..")).unwrap(), "");
+	}
+}
@@ -0,0 +1,191 @@
+use crate::{cp437, Error};
+use std::collections::HashSet;
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Duplicates {
+	#[default]
+	Allow,
+	Reject,
+	// A counter is appended to the file name, as in `name (1).txt`.
+	Rename,
+}
+
+pub(crate) enum EntryName {
+	// Bytes and whether they are UTF-8, CP437 otherwise.
+	Raw(Vec<u8>, bool),
+	Text(String),
+}
+
+pub(crate) type RawName = (Vec<u8>, bool);
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum NamePolicy {
+	// Names are written verbatim.
+	Allow,
+	// Backslashes become slashes, then drive letters, leading slashes, `.` and `..` components
+	// are removed.
+	Normalize,
+	// Empty names and names with backslashes, drive letters, leading slashes or `..` components
+	// are refused.
+	#[default]
+	Reject,
+}
+
+pub(crate) fn check(policy: NamePolicy, name: String) -> Result<String, Error> {
+	if policy == NamePolicy::Allow {
+		return Ok(name);
+	}
+	if name.contains('\0') {
+		return Err(Error::InvalidName(name));
+	}
+	if policy == NamePolicy::Reject {
+		if name.is_empty()
+			|| name.contains('\\')
+			|| name.starts_with('/')
+			|| has_drive(&name)
+			|| name.split('/').any(|component| component == "..")
+		{
+			return Err(Error::InvalidName(name));
+		}
+		return Ok(name);
+	}
+	let replaced = name.replace('\\', "/");
+	let path = match has_drive(&replaced) {
+		true => &replaced[2..],
+		false => &replaced,
+	};
+	let mut components = Vec::new();
+	for component in path.split('/') {
+		match component {
+			"" | "." => {}
+			".." => _ = components.pop(),
+			component => components.push(component),
+		}
+	}
+	if components.is_empty() {
+		return Err(Error::InvalidName(name));
+	}
+	let mut normalized = components.join("/");
+	if path.ends_with('/') {
+		normalized.push('/');
+	}
+	Ok(normalized)
+}
+
+// Link targets are resolved from the directory holding the link `name`, those that are absolute
+// or lead outside the archive are refused. The target itself is never rewritten.
+pub(crate) fn check_target(policy: NamePolicy, name: &str, target: &str) -> Result<(), Error> {
+	if policy == NamePolicy::Allow {
+		return Ok(());
+	}
+	let invalid = || Error::InvalidName(target.to_string());
+	if target.contains('\0') || target.starts_with(['/', '\\']) || has_drive(target) {
+		return Err(invalid());
+	}
+	let parent = name.trim_end_matches('/').rsplit_once('/').map_or("", |(parent, _)| parent);
+	let mut depth = 0usize;
+	for component in parent.split('/') {
+		match component {
+			"" | "." => {}
+			".." => depth = depth.saturating_sub(1),
+			_ => depth += 1,
+		}
+	}
+	for component in target.split(['/', '\\']) {
+		match component {
+			"" | "." => {}
+			".." => depth = depth.checked_sub(1).ok_or_else(invalid)?,
+			_ => depth += 1,
+		}
+	}
+	Ok(())
+}
+
+fn has_drive(name: &str) -> bool {
+	matches!(name.as_bytes(), [letter, b':', ..] if letter.is_ascii_alphabetic())
+}
+
+// Names of the entries written so far, lowercased when comparisons ignore case.
+#[derive(Default)]
+pub(crate) struct Names {
+	case_insensitive: bool,
+	duplicates: Duplicates,
+	names: HashSet<String>,
+}
+
+impl Names {
+	pub(crate) fn new(duplicates: Duplicates, case_insensitive: bool) -> Self {
+		Self {
+			case_insensitive,
+			duplicates,
+			names: HashSet::new(),
+		}
+	}
+
+	pub(crate) fn check(&self, name: String) -> Result<String, Error> {
+		if self.duplicates == Duplicates::Allow || !self.contains(&name) {
+			return Ok(name);
+		}
+		if self.duplicates == Duplicates::Reject {
+			return Err(Error::DuplicateName(name));
+		}
+		let (stem, suffix) = split(&name);
+		Ok((1..)
+			.map(|counter| format!("{stem} ({counter}){suffix}"))
+			.find(|name| !self.contains(name))
+			.unwrap())
+	}
+
+	// Raw names are never rewritten, names that would be normalized or renamed are refused
+	// instead. The decoded name is returned along with the raw bytes.
+	pub(crate) fn resolve(
+		&self,
+		policy: NamePolicy,
+		name: EntryName,
+	) -> Result<(String, Option<RawName>), Error> {
+		let (data, utf8) = match name {
+			EntryName::Raw(data, utf8) => (data, utf8),
+			EntryName::Text(name) => return Ok((self.check(check(policy, name)?)?, None)),
+		};
+		let name = match utf8 {
+			true => String::from_utf8(data.clone())
+				.map_err(|_| Error::InvalidName(String::from_utf8_lossy(&data).into_owned()))?,
+			false => cp437::decode(&data),
+		};
+		if check(policy, name.clone())? != name {
+			return Err(Error::InvalidName(name));
+		}
+		if self.check(name.clone())? != name {
+			return Err(Error::DuplicateName(name));
+		}
+		Ok((name, Some((data, utf8))))
+	}
+
+	pub(crate) fn insert(&mut self, name: &str) {
+		if self.duplicates != Duplicates::Allow {
+			let key = self.key(name);
+			self.names.insert(key);
+		}
+	}
+
+	fn contains(&self, name: &str) -> bool {
+		self.names.contains(&self.key(name))
+	}
+
+	fn key(&self, name: &str) -> String {
+		match self.case_insensitive {
+			true => name.to_lowercase(),
+			false => name.to_string(),
+		}
+	}
+}
+
+// Splits before the extension of the last component, or before the trailing slash of directories.
+fn split(name: &str) -> (&str, &str) {
+	let start = name.trim_end_matches('/').rfind('/').map_or(0, |index| index + 1);
+	let end = name.trim_end_matches('/').len();
+	match name[start..end].rfind('.') {
+		Some(0) | None => name.split_at(end),
+		Some(index) => name.split_at(start + index),
+	}
+}
@@ -0,0 +1,125 @@
+use crate::{Compression, DateTime, Encryption};
+#[cfg(unix)]
+use std::os::unix::fs::PermissionsExt;
+use std::{fs::Metadata, time::SystemTime};
+
+#[derive(Clone, Default)]
+pub struct EntryOptions {
+	pub(crate) central_extra_fields: Vec<(u16, Vec<u8>)>,
+	pub(crate) comment: String,
+	pub(crate) compression: Compression,
+	pub(crate) cp437: bool,
+	pub(crate) date_time: DateTime,
+	pub(crate) encryption: Encryption,
+	pub(crate) level: Option<u32>,
+	pub(crate) local_extra_fields: Vec<(u16, Vec<u8>)>,
+	pub(crate) ntfs: bool,
+	pub(crate) ntfs_times: Option<[SystemTime; 3]>,
+	pub(crate) owner: Option<(u32, u32)>,
+	pub(crate) permissions: Option<u32>,
+}
+
+impl EntryOptions {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn comment<T: Into<String>>(mut self, comment: T) -> Self {
+		self.comment = comment.into();
+		self
+	}
+
+	pub fn compression(mut self, compression: Compression) -> Self {
+		self.compression = compression;
+		self
+	}
+
+	// Encodes the name and comment in CP437 instead of UTF-8, for readers that ignore the UTF-8
+	// flag. Text that CP437 cannot represent is also stored in Unicode extra fields.
+	pub fn cp437(mut self, cp437: bool) -> Self {
+		self.cp437 = cp437;
+		self
+	}
+
+	pub fn date_time(mut self, date_time: DateTime) -> Self {
+		self.date_time = date_time;
+		self
+	}
+
+	pub fn encryption(mut self, encryption: Encryption) -> Self {
+		self.encryption = encryption;
+		self
+	}
+
+	// Adds a field to both the local header and the central directory.
+	pub fn extra_field<T: Into<Vec<u8>>>(self, id: u16, data: T) -> Self {
+		let data = data.into();
+		self.local_extra_field(id, data.clone()).central_extra_field(id, data)
+	}
+
+	pub fn central_extra_field<T: Into<Vec<u8>>>(mut self, id: u16, data: T) -> Self {
+		self.central_extra_fields.push((id, data.into()));
+		self
+	}
+
+	pub fn local_extra_field<T: Into<Vec<u8>>>(mut self, id: u16, data: T) -> Self {
+		self.local_extra_fields.push((id, data.into()));
+		self
+	}
+
+	// Levels range from 0 to 9, higher values are clamped to 9.
+	pub fn level(mut self, level: u32) -> Self {
+		self.level = Some(level.min(9));
+		self
+	}
+
+	// Writes the NTFS extra field. Times are read from the file by `add_file` and `add_dir_all`, and
+	// derived from the date and time of the entry otherwise.
+	pub fn ntfs(mut self, ntfs: bool) -> Self {
+		self.ntfs = ntfs;
+		self
+	}
+
+	// Writes the NTFS extra field with the given times, the modification time also sets the date
+	// and time of the entry.
+	pub fn ntfs_times(
+		mut self,
+		modified: SystemTime,
+		accessed: SystemTime,
+		created: SystemTime,
+	) -> Self {
+		self.date_time = modified.into();
+		self.ntfs = true;
+		self.ntfs_times = Some([modified, accessed, created]);
+		self
+	}
+
+	// Unix user and group identifiers, restored by `unzip -X`.
+	pub fn owner(mut self, uid: u32, gid: u32) -> Self {
+		self.owner = Some((uid, gid));
+		self
+	}
+
+	// Unix mode, stored in the external attributes of the central directory. Regular file is
+	// assumed when the mode does not include a file type.
+	pub fn permissions(mut self, mode: u32) -> Self {
+		self.permissions = Some(mode);
+		self
+	}
+
+	pub(crate) fn metadata(mut self, metadata: &Metadata) -> Self {
+		if let Ok(modified) = metadata.modified() {
+			self.date_time = modified.into();
+			if self.ntfs {
+				let accessed = metadata.accessed().unwrap_or(modified);
+				let created = metadata.created().unwrap_or(modified);
+				self.ntfs_times = Some([modified, accessed, created]);
+			}
+		}
+		#[cfg(unix)]
+		{
+			self.permissions = Some(metadata.permissions().mode());
+		}
+		self
+	}
+}
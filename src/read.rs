@@ -0,0 +1,593 @@
+use crate::{
+	attributes,
+	central::{self, read_u16, read_u32},
+	cp437, DateTime, Entry, Error, CENTRAL_DIRECTORY_HEADER, DATA_DESCRIPTOR,
+	END_CENTRAL_DIRECTORY, FLAG_DESCRIPTOR, FLAG_ENCRYPTED, FLAG_UTF8, LOCAL_HEADER,
+};
+#[cfg(feature = "crc")]
+use crc32fast::Hasher;
+#[cfg(feature = "deflate")]
+use flate2::{read::DeflateDecoder, Decompress, FlushDecompress, Status};
+use std::io::{self, Read, Seek, SeekFrom, Take};
+#[cfg(feature = "tokio")]
+use std::{
+	future::poll_fn,
+	pin::Pin,
+	task::{ready, Context, Poll},
+};
+#[cfg(feature = "tokio")]
+use tokio::io::{self as tokio_io, AsyncRead, ReadBuf};
+
+const CHUNK_SIZE: usize = 8192;
+
+// Entries are located through the central directory, so that their sizes are known before
+// reading them.
+pub struct Zip<R: Read + Seek> {
+	pub(crate) central_directory_offset: u64,
+	entries: Vec<Entry>,
+	reader: R,
+}
+
+impl<R: Read + Seek> Zip<R> {
+	pub fn new(mut reader: R) -> Result<Self, Error> {
+		let (end, entries) = central::read(&mut reader)?;
+		Ok(Self {
+			central_directory_offset: end.offset,
+			entries,
+			reader,
+		})
+	}
+
+	pub fn entries(&self) -> &[Entry] {
+		&self.entries
+	}
+
+	pub fn by_name(&mut self, name: &str) -> Result<EntryReader<'_, R>, Error> {
+		match self.entries.iter().position(|entry| entry.name == name) {
+			Some(index) => self.entry(index),
+			None => Err(Error::UnknownEntry(name.to_string())),
+		}
+	}
+
+	// Panics if `index` is out of bounds, as indexing `entries` would.
+	pub fn entry(&mut self, index: usize) -> Result<EntryReader<'_, R>, Error> {
+		let entry = &self.entries[index];
+		if entry.flags & FLAG_ENCRYPTED != 0 {
+			return Err(Error::Unsupported("encrypted entries"));
+		}
+		let start = entry.position + self.local_header(entry.position)?.len() as u64;
+		let entry = &self.entries[index];
+		let data = (&mut self.reader).take(entry.size);
+		let decoder = match entry.method {
+			#[cfg(feature = "deflate")]
+			8 => Decoder::Deflate(DeflateDecoder::new(data)),
+			0 => Decoder::Stored(data),
+			_ => {
+				return Err(Error::Unsupported(
+					"compression methods other than store and deflate",
+				))
+			}
+		};
+		Ok(EntryReader {
+			#[cfg(feature = "crc")]
+			crc: Hasher::new(),
+			decoder,
+			done: false,
+			entry,
+			raw_size: 0,
+			start,
+		})
+	}
+
+	pub fn into_inner(self) -> R {
+		self.reader
+	}
+
+	// Returns the local header along with the entry data, as stored.
+	pub(crate) fn raw(&mut self, index: usize) -> Result<(Vec<u8>, Take<&mut R>), Error> {
+		let header = self.local_header(self.entries[index].position)?;
+		Ok((header, (&mut self.reader).take(self.entries[index].size)))
+	}
+
+	// Leaves the reader after the header, at the start of the entry data.
+	pub(crate) fn local_header(&mut self, position: u64) -> Result<Vec<u8>, Error> {
+		let mut header = self.read_at(position, 30)?;
+		if !header.starts_with(LOCAL_HEADER) {
+			return Err(Error::InvalidArchive("local header expected"));
+		}
+		let length = usize::from(read_u16(&header, 26)) + usize::from(read_u16(&header, 28));
+		header.resize(30 + length, 0);
+		self.reader.read_exact(&mut header[30..])?;
+		Ok(header)
+	}
+
+	pub(crate) fn read_at(&mut self, position: u64, length: usize) -> Result<Vec<u8>, Error> {
+		let mut data = vec![0; length];
+		self.reader.seek(SeekFrom::Start(position))?;
+		self.reader.read_exact(&mut data)?;
+		Ok(data)
+	}
+}
+
+enum Decoder<'a, R: Read> {
+	#[cfg(feature = "deflate")]
+	Deflate(DeflateDecoder<Take<&'a mut R>>),
+	Stored(Take<&'a mut R>),
+}
+
+pub struct EntryReader<'a, R: Read + Seek> {
+	#[cfg(feature = "crc")]
+	crc: Hasher,
+	decoder: Decoder<'a, R>,
+	done: bool,
+	entry: &'a Entry,
+	raw_size: u64,
+	start: u64,
+}
+
+impl<R: Read + Seek> EntryReader<'_, R> {
+	pub fn entry(&self) -> &Entry {
+		self.entry
+	}
+
+	// Sizes are checked against the central directory, along with the data descriptor and the
+	// checksum when the crc feature is enabled.
+	fn check(&mut self) -> Result<(), Error> {
+		let entry = self.entry;
+		if self.raw_size != entry.raw_size {
+			return Err(Error::InvalidArchive(
+				"entry size does not match the central directory",
+			));
+		}
+		#[cfg(feature = "crc")]
+		if self.crc.clone().finalize() != entry.crc {
+			return Err(Error::ChecksumMismatch(entry.name.clone()));
+		}
+		if entry.flags & FLAG_DESCRIPTOR == 0 {
+			return Ok(());
+		}
+		let reader = match &mut self.decoder {
+			#[cfg(feature = "deflate")]
+			Decoder::Deflate(decoder) => decoder.get_mut().get_mut(),
+			Decoder::Stored(data) => data.get_mut(),
+		};
+		reader.seek(SeekFrom::Start(self.start + entry.size))?;
+		let mut descriptor = [0; 16];
+		reader.read_exact(&mut descriptor)?;
+		match descriptor_length(&descriptor, Some(entry.crc), entry.size, entry.raw_size) {
+			Some(_) => Ok(()),
+			None => Err(Error::InvalidArchive(
+				"data descriptor does not match the central directory",
+			)),
+		}
+	}
+}
+
+impl<R: Read + Seek> Read for EntryReader<'_, R> {
+	fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+		let size = match &mut self.decoder {
+			#[cfg(feature = "deflate")]
+			Decoder::Deflate(decoder) => decoder.read(buffer),
+			Decoder::Stored(data) => data.read(buffer),
+		}?;
+		#[cfg(feature = "crc")]
+		self.crc.update(&buffer[..size]);
+		self.raw_size += size as u64;
+		if size == 0 && !buffer.is_empty() && !self.done {
+			self.done = true;
+			self.check()?;
+		}
+		Ok(size)
+	}
+}
+
+// Entries are read one after the other without seeking, so that archives can be read as they
+// are received.
+pub struct Stream<R: Read> {
+	parser: Parser,
+	reader: R,
+}
+
+impl<R: Read> Stream<R> {
+	pub fn new(reader: R) -> Self {
+		Self {
+			parser: Parser::default(),
+			reader,
+		}
+	}
+
+	// The rest of the previous entry is skipped. `None` is returned once the central directory is
+	// reached.
+	pub fn next_entry(&mut self) -> Result<Option<StreamEntry<'_, R>>, Error> {
+		if self.parser.current.is_some() {
+			io::copy(&mut StreamEntry { stream: self }, &mut io::sink())?;
+		}
+		loop {
+			match self.parser.header()? {
+				Some(true) => return Ok(Some(StreamEntry { stream: self })),
+				Some(false) => return Ok(None),
+				None => self.fill()?,
+			}
+		}
+	}
+
+	pub fn into_inner(self) -> R {
+		self.reader
+	}
+
+	fn fill(&mut self) -> Result<(), Error> {
+		let mut chunk = [0; CHUNK_SIZE];
+		let size = self.reader.read(&mut chunk)?;
+		if size == 0 {
+			return Err(Error::InvalidArchive("archive is truncated"));
+		}
+		self.parser.buffer.extend(&chunk[..size]);
+		Ok(())
+	}
+}
+
+pub struct StreamEntry<'a, R: Read> {
+	stream: &'a mut Stream<R>,
+}
+
+impl<R: Read> StreamEntry<'_, R> {
+	// The checksum and sizes of entries written with a data descriptor are only known once they
+	// have been read.
+	pub fn entry(&self) -> &Entry {
+		&self.stream.parser.current.as_ref().unwrap().entry
+	}
+}
+
+impl<R: Read> Read for StreamEntry<'_, R> {
+	fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+		loop {
+			match self.stream.parser.decode(buffer)? {
+				Some(size) => return Ok(size),
+				None => self.stream.fill()?,
+			}
+		}
+	}
+}
+
+#[cfg(feature = "tokio")]
+pub struct AsyncStream<R: AsyncRead + Unpin> {
+	parser: Parser,
+	reader: R,
+}
+
+#[cfg(feature = "tokio")]
+impl<R: AsyncRead + Unpin> AsyncStream<R> {
+	pub fn new(reader: R) -> Self {
+		Self {
+			parser: Parser::default(),
+			reader,
+		}
+	}
+
+	pub async fn next_entry(&mut self) -> Result<Option<AsyncStreamEntry<'_, R>>, Error> {
+		if self.parser.current.is_some() {
+			tokio_io::copy(
+				&mut AsyncStreamEntry { stream: self },
+				&mut tokio_io::sink(),
+			)
+			.await?;
+		}
+		loop {
+			match self.parser.header()? {
+				Some(true) => return Ok(Some(AsyncStreamEntry { stream: self })),
+				Some(false) => return Ok(None),
+				None => poll_fn(|context| self.poll_fill(context)).await?,
+			}
+		}
+	}
+
+	pub fn into_inner(self) -> R {
+		self.reader
+	}
+
+	fn poll_fill(&mut self, context: &mut Context<'_>) -> Poll<Result<(), Error>> {
+		let mut chunk = [0; CHUNK_SIZE];
+		let mut buffer = ReadBuf::new(&mut chunk);
+		ready!(Pin::new(&mut self.reader).poll_read(context, &mut buffer))?;
+		if buffer.filled().is_empty() {
+			return Poll::Ready(Err(Error::InvalidArchive("archive is truncated")));
+		}
+		self.parser.buffer.extend(buffer.filled());
+		Poll::Ready(Ok(()))
+	}
+}
+
+#[cfg(feature = "tokio")]
+pub struct AsyncStreamEntry<'a, R: AsyncRead + Unpin> {
+	stream: &'a mut AsyncStream<R>,
+}
+
+#[cfg(feature = "tokio")]
+impl<R: AsyncRead + Unpin> AsyncStreamEntry<'_, R> {
+	pub fn entry(&self) -> &Entry {
+		&self.stream.parser.current.as_ref().unwrap().entry
+	}
+}
+
+#[cfg(feature = "tokio")]
+impl<R: AsyncRead + Unpin> AsyncRead for AsyncStreamEntry<'_, R> {
+	fn poll_read(
+		self: Pin<&mut Self>,
+		context: &mut Context<'_>,
+		buffer: &mut ReadBuf<'_>,
+	) -> Poll<io::Result<()>> {
+		let stream = &mut self.get_mut().stream;
+		loop {
+			match stream.parser.decode(buffer.initialize_unfilled())? {
+				Some(size) => {
+					buffer.advance(size);
+					return Poll::Ready(Ok(()));
+				}
+				None => ready!(stream.poll_fill(context))?,
+			}
+		}
+	}
+}
+
+enum Data {
+	#[cfg(feature = "deflate")]
+	Deflate(Decompress),
+	// Data has been read, the data descriptor or the header values remain to be checked.
+	End,
+	// Stored entries followed by a data descriptor end where a matching descriptor is found.
+	Scan,
+	Stored(u64),
+}
+
+struct Current {
+	#[cfg(feature = "crc")]
+	crc: Hasher,
+	data: Data,
+	done: bool,
+	entry: Entry,
+	raw_size: u64,
+	size: u64,
+}
+
+// Input is buffered and decoded without performing any I/O, so that the blocking and
+// asynchronous streams share the parsing. `None` is returned when more input is needed.
+#[derive(Default)]
+struct Parser {
+	buffer: Vec<u8>,
+	current: Option<Current>,
+	finished: bool,
+	offset: u64,
+}
+
+impl Parser {
+	fn consume(&mut self, length: usize) {
+		self.buffer.drain(..length);
+		self.offset += length as u64;
+	}
+
+	// Returns whether an entry was started, or the central directory reached.
+	fn header(&mut self) -> Result<Option<bool>, Error> {
+		if self.finished {
+			return Ok(Some(false));
+		}
+		self.current = None;
+		if self.buffer.len() < 4 {
+			return Ok(None);
+		}
+		if self.buffer.starts_with(CENTRAL_DIRECTORY_HEADER)
+			|| self.buffer.starts_with(END_CENTRAL_DIRECTORY)
+		{
+			self.finished = true;
+			return Ok(Some(false));
+		}
+		if !self.buffer.starts_with(LOCAL_HEADER) {
+			return Err(Error::InvalidArchive("local header expected"));
+		}
+		if self.buffer.len() < 30 {
+			return Ok(None);
+		}
+		let header = &self.buffer;
+		let name_end = 30 + usize::from(read_u16(header, 26));
+		let length = name_end + usize::from(read_u16(header, 28));
+		if header.len() < length {
+			return Ok(None);
+		}
+		let (flags, method) = (read_u16(header, 6), read_u16(header, 8));
+		if flags & FLAG_ENCRYPTED != 0 {
+			return Err(Error::Unsupported("encrypted entries"));
+		}
+		let size = read_u32(header, 18);
+		let data = match (method, flags & FLAG_DESCRIPTOR) {
+			#[cfg(feature = "deflate")]
+			(8, _) => Data::Deflate(Decompress::new(false)),
+			(0, 0) => Data::Stored(size.into()),
+			(0, _) => Data::Scan,
+			_ => {
+				return Err(Error::Unsupported(
+					"compression methods other than store and deflate",
+				))
+			}
+		};
+		let raw_name = header[30..name_end].to_vec();
+		let extra_field = header[name_end..length].to_vec();
+		let name = match flags & FLAG_UTF8 {
+			0 => cp437::decode_name(&raw_name, &extra_field),
+			_ => String::from_utf8_lossy(&raw_name).into_owned(),
+		};
+		// Local headers do not record the system the entry was made on.
+		let (version_made_by, external_attributes) = attributes(None);
+		let entry = Entry {
+			comment: String::new(),
+			crc: read_u32(header, 14),
+			date_time: DateTime::from_le_bytes([header[10], header[11], header[12], header[13]]),
+			external_attributes,
+			extra_field,
+			flags,
+			internal_attributes: 0,
+			method,
+			name,
+			permissions: None,
+			position: self.offset,
+			raw_comment: Vec::new(),
+			raw_name,
+			raw_size: read_u32(header, 22).into(),
+			size: size.into(),
+			version: read_u16(header, 4),
+			version_made_by,
+		};
+		self.consume(length);
+		self.current = Some(Current {
+			#[cfg(feature = "crc")]
+			crc: Hasher::new(),
+			data,
+			done: false,
+			entry,
+			raw_size: 0,
+			size: 0,
+		});
+		Ok(Some(true))
+	}
+
+	// Returns the size of the decoded data, 0 once the entry has been checked.
+	fn decode(&mut self, output: &mut [u8]) -> Result<Option<usize>, Error> {
+		loop {
+			let Some(current) = &mut self.current else {
+				return Ok(Some(0));
+			};
+			if current.done || output.is_empty() {
+				return Ok(Some(0));
+			}
+			let buffer = &self.buffer;
+			let (consumed, produced, end) = match &mut current.data {
+				#[cfg(feature = "deflate")]
+				Data::Deflate(decompress) => {
+					let (total_in, total_out) = (decompress.total_in(), decompress.total_out());
+					let status = decompress
+						.decompress(buffer, output, FlushDecompress::None)
+						.map_err(|_| Error::InvalidArchive("deflate data is corrupted"))?;
+					(
+						(decompress.total_in() - total_in) as usize,
+						(decompress.total_out() - total_out) as usize,
+						status == Status::StreamEnd,
+					)
+				}
+				Data::End => (0, 0, true),
+				Data::Scan => {
+					// Bytes that could start a descriptor are held back until the signature
+					// following it is available.
+					let limit = buffer.len().saturating_sub(19);
+					let end = (0..limit).find(|&i| current.is_descriptor(buffer, i));
+					let length = end.unwrap_or(limit).min(output.len());
+					output[..length].copy_from_slice(&buffer[..length]);
+					(length, length, end == Some(length))
+				}
+				Data::Stored(remaining) => {
+					let length = buffer.len().min(output.len()).min(*remaining as usize);
+					output[..length].copy_from_slice(&buffer[..length]);
+					*remaining -= length as u64;
+					(length, length, *remaining == 0)
+				}
+			};
+			#[cfg(feature = "crc")]
+			current.crc.update(&output[..produced]);
+			current.raw_size += produced as u64;
+			current.size += consumed as u64;
+			if end {
+				current.data = Data::End;
+			}
+			self.consume(consumed);
+			if produced > 0 {
+				return Ok(Some(produced));
+			}
+			if end {
+				return Ok(self.check()?.map(|_| 0));
+			}
+			if consumed == 0 {
+				return Ok(None);
+			}
+		}
+	}
+
+	// Data descriptors, or the header values otherwise, must match the decoded data.
+	fn check(&mut self) -> Result<Option<()>, Error> {
+		let current = self.current.as_mut().unwrap();
+		#[cfg(feature = "crc")]
+		let crc = Some(current.crc.clone().finalize());
+		#[cfg(not(feature = "crc"))]
+		let crc: Option<u32> = None;
+		let entry = &mut current.entry;
+		let length = match entry.flags & FLAG_DESCRIPTOR {
+			0 => {
+				if current.size != entry.size || current.raw_size != entry.raw_size {
+					return Err(Error::InvalidArchive(
+						"entry size does not match its header",
+					));
+				}
+				0
+			}
+			_ => {
+				if self.buffer.len() < 16 {
+					return Ok(None);
+				}
+				let descriptor = &self.buffer[..16];
+				let length = descriptor_length(descriptor, None, current.size, current.raw_size)
+					.ok_or(Error::InvalidArchive(
+						"data descriptor does not match the entry",
+					))?;
+				entry.crc = read_u32(descriptor, length - 12);
+				(entry.size, entry.raw_size) = (current.size, current.raw_size);
+				length
+			}
+		};
+		if crc.is_some_and(|crc| crc != entry.crc) {
+			return Err(Error::ChecksumMismatch(entry.name.clone()));
+		}
+		current.done = true;
+		self.consume(length);
+		Ok(Some(()))
+	}
+}
+
+impl Current {
+	// A descriptor starting `offset` bytes into `buffer` must match the data before it, and be
+	// followed by another header. 20 bytes are needed from `offset`.
+	fn is_descriptor(&self, buffer: &[u8], offset: usize) -> bool {
+		let (data, size) = (&buffer[offset..offset + 20], self.raw_size + offset as u64);
+		// Both layouts have the uncompressed size at the same position.
+		if u64::from(read_u32(data, 8)) != size {
+			return false;
+		}
+		#[cfg(feature = "crc")]
+		let crc = {
+			let mut crc = self.crc.clone();
+			crc.update(&buffer[..offset]);
+			Some(crc.finalize())
+		};
+		#[cfg(not(feature = "crc"))]
+		let crc: Option<u32> = None;
+		descriptor_length(data, crc, size, size).is_some_and(|length| {
+			data[length..].starts_with(LOCAL_HEADER)
+				|| data[length..].starts_with(CENTRAL_DIRECTORY_HEADER)
+		})
+	}
+}
+
+// The signature of data descriptors is optional, both layouts are tried against the expected
+// values. The checksum is not compared when it is unknown. The length of the descriptor is
+// returned when one of them matches.
+pub(crate) fn descriptor_length(
+	data: &[u8],
+	crc: Option<u32>,
+	size: u64,
+	raw_size: u64,
+) -> Option<usize> {
+	let matches = |data: &[u8]| {
+		crc.is_none_or(|crc| read_u32(data, 0) == crc)
+			&& u64::from(read_u32(data, 4)) == size
+			&& u64::from(read_u32(data, 8)) == raw_size
+	};
+	if data.starts_with(DATA_DESCRIPTOR) && matches(&data[4..]) {
+		return Some(16);
+	}
+	matches(data).then_some(12)
+}
@@ -1,7 +1,36 @@
-use crate::{Compression, DateTime, Zip};
-use std::io::Write;
+use crate::{
+	read, walk, Action, Compression, DateTime, Duplicates, EntryOptions, Error, Filter, NamePolicy,
+	Zip, DATA_DESCRIPTOR,
+};
+#[cfg(feature = "aes")]
+use crate::{AesStrength, AesVersion, Encryption};
+#[cfg(feature = "aes")]
+use aes::{
+	cipher::{KeyIvInit, StreamCipher},
+	Aes256,
+};
+#[cfg(feature = "aes")]
+use hmac::{Hmac, Mac};
+#[cfg(feature = "aes")]
+use sha1::Sha1;
+#[cfg(unix)]
+use std::{
+	env,
+	fs::{self, File, Permissions},
+	os::unix::fs::{symlink, PermissionsExt},
+	path::PathBuf,
+};
+use std::{
+	io::{self, Cursor, ErrorKind, Read, Write},
+	time::{Duration, SystemTime, UNIX_EPOCH},
+};
 #[cfg(feature = "tokio")]
-use tokio::io::AsyncWriteExt;
+use std::{
+	pin::Pin,
+	task::{Context, Poll},
+};
+#[cfg(feature = "tokio")]
+use tokio::io::{AsyncReadExt, AsyncWrite, AsyncWriteExt};
 
 const NO_ENTRIES: &[u8] = &[
 	0x50, 0x4B, 0x05, 0x06, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
@@ -13,12 +42,13 @@ const ONE_COMPRESSED_ENTRY: &[u8] = &[
 	0x50, 0x4B, 0x03, 0x04, 0x14, 0x00, 0b00001000, 0b00001000, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00,
 	0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x05, 0x00, 0x00, 0x00,
 	b'1', b'.', b't', b'x', b't', 0x0A, 0xCE, 0xCF, 0x4D, 0x55, 0x48, 0x49, 0x2C, 0x49, 0xE4, 0x02,
-	0x00, 0x00, 0x00, 0xFF, 0xFF, 0x03, 0x00, 0xC9, 0xFA, 0x5C, 0x87, 0x12, 0x00, 0x00, 0x00, 0x0A,
-	0x00, 0x00, 0x00, 0x50, 0x4B, 0x01, 0x02, 0x00, 0x00, 0x14, 0x00, 0b00001000, 0b00001000, 0x08,
-	0x00, 0x00, 0x00, 0x00, 0x00, 0xC9, 0xFA, 0x5C, 0x87, 0x12, 0x00, 0x00, 0x00, 0x0A, 0x00, 0x00,
-	0x00, 0x05, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-	0x00, 0x00, 0x00, b'1', b'.', b't', b'x', b't', 0x50, 0x4B, 0x05, 0x06, 0x00, 0x00, 0x00, 0x00,
-	0x01, 0x00, 0x01, 0x00, 0x33, 0x00, 0x00, 0x00, 0x41, 0x00, 0x00, 0x00, 0x00, 0x00,
+	0x00, 0x00, 0x00, 0xFF, 0xFF, 0x03, 0x00, 0x50, 0x4B, 0x07, 0x08, 0xC9, 0xFA, 0x5C, 0x87, 0x12,
+	0x00, 0x00, 0x00, 0x0A, 0x00, 0x00, 0x00, 0x50, 0x4B, 0x01, 0x02, 0x3F, 0x00, 0x14, 0x00,
+	0b00001000, 0b00001000, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0xC9, 0xFA, 0x5C, 0x87, 0x12, 0x00,
+	0x00, 0x00, 0x0A, 0x00, 0x00, 0x00, 0x05, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+	0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, b'1', b'.', b't', b'x', b't', 0x50, 0x4B, 0x05,
+	0x06, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x01, 0x00, 0x33, 0x00, 0x00, 0x00, 0x45, 0x00, 0x00,
+	0x00, 0x00, 0x00,
 ];
 
 #[cfg(feature = "deflate")]
@@ -26,65 +56,136 @@ const ONE_COMPRESSED_ENTRY: &[u8] = &[
 macro_rules! one_compressed_entry_no_crc {
 	() => {{
 		let mut data = ONE_COMPRESSED_ENTRY.to_vec();
-		data[53..57].copy_from_slice(&[0, 0, 0, 0]);
-		data[81..85].copy_from_slice(&[0, 0, 0, 0]);
+		data[57..61].copy_from_slice(&[0, 0, 0, 0]);
+		data[85..89].copy_from_slice(&[0, 0, 0, 0]);
 		data
 	}};
 }
 
 const ONE_UNCOMPRESSED_ENTRY: &[u8] = &[
-	0x50, 0x4B, 0x03, 0x04, 0x14, 0x00, 0b00001000, 0b00001000, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+	0x50, 0x4B, 0x03, 0x04, 0x0A, 0x00, 0b00001000, 0b00001000, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
 	0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x05, 0x00, 0x00, 0x00,
 	b'1', b'.', b't', b'x', b't', b'S', b'o', b'm', b'e', b' ', b'd', b'a', b't', b'a', b'\n',
-	0xC9, 0xFA, 0x5C, 0x87, 0x0A, 0x00, 0x00, 0x00, 0x0A, 0x00, 0x00, 0x00, 0x50, 0x4B, 0x01, 0x02,
-	0x00, 0x00, 0x14, 0x00, 0b00001000, 0b00001000, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xC9, 0xFA,
-	0x5C, 0x87, 0x0A, 0x00, 0x00, 0x00, 0x0A, 0x00, 0x00, 0x00, 0x05, 0x00, 0x00, 0x00, 0x00, 0x00,
-	0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, b'1', b'.', b't', b'x',
-	b't', 0x50, 0x4B, 0x05, 0x06, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x01, 0x00, 0x33, 0x00, 0x00,
-	0x00, 0x39, 0x00, 0x00, 0x00, 0x00, 0x00,
+	0x50, 0x4B, 0x07, 0x08, 0xC9, 0xFA, 0x5C, 0x87, 0x0A, 0x00, 0x00, 0x00, 0x0A, 0x00, 0x00, 0x00,
+	0x50, 0x4B, 0x01, 0x02, 0x3F, 0x00, 0x0A, 0x00, 0b00001000, 0b00001000, 0x00, 0x00, 0x00, 0x00,
+	0x00, 0x00, 0xC9, 0xFA, 0x5C, 0x87, 0x0A, 0x00, 0x00, 0x00, 0x0A, 0x00, 0x00, 0x00, 0x05, 0x00,
+	0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+	b'1', b'.', b't', b'x', b't', 0x50, 0x4B, 0x05, 0x06, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x01,
+	0x00, 0x33, 0x00, 0x00, 0x00, 0x3D, 0x00, 0x00, 0x00, 0x00, 0x00,
 ];
 
 #[cfg(not(feature = "crc"))]
 macro_rules! one_uncompressed_entry_no_crc {
 	() => {{
 		let mut data = ONE_UNCOMPRESSED_ENTRY.to_vec();
-		data[45..49].copy_from_slice(&[0, 0, 0, 0]);
-		data[73..77].copy_from_slice(&[0, 0, 0, 0]);
+		data[49..53].copy_from_slice(&[0, 0, 0, 0]);
+		data[77..81].copy_from_slice(&[0, 0, 0, 0]);
 		data
 	}};
 }
 
 const TWO_ENTRIES: &[u8] = &[
-	0x50, 0x4B, 0x03, 0x04, 0x14, 0x00, 0b00001000, 0b00001000, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+	0x50, 0x4B, 0x03, 0x04, 0x0A, 0x00, 0b00001000, 0b00001000, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
 	0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x05, 0x00, 0x00, 0x00,
 	b'1', b'.', b't', b'x', b't', b'S', b'o', b'm', b'e', b' ', b'd', b'a', b't', b'a', b'\n',
-	0xC9, 0xFA, 0x5C, 0x87, 0x0A, 0x00, 0x00, 0x00, 0x0A, 0x00, 0x00, 0x00, 0x50, 0x4B, 0x03, 0x04,
-	0x14, 0x00, 0b00001000, 0b00001000, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-	0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x05, 0x00, 0x00, 0x00, b'2', b'.', b't', b'x',
-	b't', b'S', b'o', b'm', b'e', b' ', b'm', b'o', b'r', b'e', b' ', b'd', b'a', b't', b'a',
-	b'\n', 0x2F, 0x9B, 0xBB, 0x5A, 0x0F, 0x00, 0x00, 0x00, 0x0F, 0x00, 0x00, 0x00, 0x50, 0x4B,
-	0x01, 0x02, 0x00, 0x00, 0x14, 0x00, 0b00001000, 0b00001000, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-	0xC9, 0xFA, 0x5C, 0x87, 0x0A, 0x00, 0x00, 0x00, 0x0A, 0x00, 0x00, 0x00, 0x05, 0x00, 0x00, 0x00,
-	0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, b'1', b'.',
-	b't', b'x', b't', 0x50, 0x4B, 0x01, 0x02, 0x00, 0x00, 0x14, 0x00, 0b00001000, 0b00001000, 0x00,
-	0x00, 0x00, 0x00, 0x00, 0x00, 0x2F, 0x9B, 0xBB, 0x5A, 0x0F, 0x00, 0x00, 0x00, 0x0F, 0x00, 0x00,
-	0x00, 0x05, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x39,
-	0x00, 0x00, 0x00, b'2', b'.', b't', b'x', b't', 0x50, 0x4B, 0x05, 0x06, 0x00, 0x00, 0x00, 0x00,
-	0x02, 0x00, 0x02, 0x00, 0x66, 0x00, 0x00, 0x00, 0x77, 0x00, 0x00, 0x00, 0x00, 0x00,
+	0x50, 0x4B, 0x07, 0x08, 0xC9, 0xFA, 0x5C, 0x87, 0x0A, 0x00, 0x00, 0x00, 0x0A, 0x00, 0x00, 0x00,
+	0x50, 0x4B, 0x03, 0x04, 0x0A, 0x00, 0b00001000, 0b00001000, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+	0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x05, 0x00, 0x00, 0x00,
+	b'2', b'.', b't', b'x', b't', b'S', b'o', b'm', b'e', b' ', b'm', b'o', b'r', b'e', b' ', b'd',
+	b'a', b't', b'a', b'\n', 0x50, 0x4B, 0x07, 0x08, 0x2F, 0x9B, 0xBB, 0x5A, 0x0F, 0x00, 0x00,
+	0x00, 0x0F, 0x00, 0x00, 0x00, 0x50, 0x4B, 0x01, 0x02, 0x3F, 0x00, 0x0A, 0x00, 0b00001000,
+	0b00001000, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xC9, 0xFA, 0x5C, 0x87, 0x0A, 0x00, 0x00, 0x00,
+	0x0A, 0x00, 0x00, 0x00, 0x05, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+	0x00, 0x00, 0x00, 0x00, 0x00, 0x00, b'1', b'.', b't', b'x', b't', 0x50, 0x4B, 0x01, 0x02, 0x3F,
+	0x00, 0x0A, 0x00, 0b00001000, 0b00001000, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x2F, 0x9B, 0xBB,
+	0x5A, 0x0F, 0x00, 0x00, 0x00, 0x0F, 0x00, 0x00, 0x00, 0x05, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+	0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x3D, 0x00, 0x00, 0x00, b'2', b'.', b't', b'x', b't',
+	0x50, 0x4B, 0x05, 0x06, 0x00, 0x00, 0x00, 0x00, 0x02, 0x00, 0x02, 0x00, 0x66, 0x00, 0x00, 0x00,
+	0x7F, 0x00, 0x00, 0x00, 0x00, 0x00,
 ];
 
 #[cfg(not(feature = "crc"))]
 macro_rules! two_entries_no_crc {
 	() => {{
 		let mut data = TWO_ENTRIES.to_vec();
-		data[45..49].copy_from_slice(&[0, 0, 0, 0]);
-		data[107..111].copy_from_slice(&[0, 0, 0, 0]);
-		data[135..139].copy_from_slice(&[0, 0, 0, 0]);
-		data[186..190].copy_from_slice(&[0, 0, 0, 0]);
+		data[49..53].copy_from_slice(&[0, 0, 0, 0]);
+		data[115..119].copy_from_slice(&[0, 0, 0, 0]);
+		data[143..147].copy_from_slice(&[0, 0, 0, 0]);
+		data[194..198].copy_from_slice(&[0, 0, 0, 0]);
 		data
 	}};
 }
 
+#[cfg(feature = "aes")]
+const ONE_ENCRYPTED_ENTRY_HEADER: &[u8] = &[
+	0x50, 0x4B, 0x03, 0x04, 0x33, 0x00, 0b00001001, 0b00001000, 0x63, 0x00, 0x00, 0x00, 0x00, 0x00,
+	0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x05, 0x00, 0x0B, 0x00,
+	b'1', b'.', b't', b'x', b't', 0x01, 0x99, 0x07, 0x00, 0x02, 0x00, b'A', b'E', 0x03, 0x00, 0x00,
+];
+
+#[cfg(feature = "aes")]
+fn decrypt_aes256(password: &str, data: &[u8]) -> Vec<u8> {
+	let (salt, data) = data.split_at(16);
+	let (verifier, data) = data.split_at(2);
+	let (data, code) = data.split_at(data.len() - 10);
+	let mut key = [0; 66];
+	pbkdf2::pbkdf2_hmac::<Sha1>(password.as_bytes(), salt, 1000, &mut key);
+	assert_eq!(verifier, &key[64..]);
+	let mut mac = Hmac::<Sha1>::new_from_slice(&key[32..64]).unwrap();
+	mac.update(data);
+	assert_eq!(code, &mac.finalize().into_bytes()[..10]);
+	let mut iv = [0; 16];
+	iv[0] = 1;
+	let mut data = data.to_vec();
+	ctr::Ctr128LE::<Aes256>::new(key[..32].into(), &iv.into()).apply_keystream(&mut data);
+	data
+}
+
+struct FailingWriter {
+	remaining: usize,
+	// Fails once with a timeout instead of a broken pipe, then accepts everything.
+	timeout: bool,
+}
+
+impl Write for FailingWriter {
+	fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+		if self.remaining == 0 && self.timeout {
+			self.remaining = usize::MAX;
+			self.timeout = false;
+			return Err(ErrorKind::TimedOut.into());
+		}
+		if self.remaining == 0 {
+			return Err(ErrorKind::BrokenPipe.into());
+		}
+		let size = data.len().min(self.remaining);
+		self.remaining -= size;
+		Ok(size)
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		Ok(())
+	}
+}
+
+#[cfg(feature = "tokio")]
+impl AsyncWrite for FailingWriter {
+	fn poll_write(
+		mut self: Pin<&mut Self>,
+		_: &mut Context<'_>,
+		data: &[u8],
+	) -> Poll<io::Result<usize>> {
+		Poll::Ready(Write::write(&mut *self, data))
+	}
+
+	fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+		Poll::Ready(Ok(()))
+	}
+
+	fn poll_shutdown(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+		Poll::Ready(Ok(()))
+	}
+}
+
 #[test]
 fn no_entries() {
 	let mut data = Vec::new();
@@ -98,8 +199,14 @@ fn no_entries() {
 fn one_compressed_entry() {
 	let mut data = Vec::new();
 	let mut writer = Zip::new(&mut data);
-	assert!(writer.create_entry("1.txt", Compression::Deflate, DateTime::default()).is_ok());
-	assert!(writer.write_all(b"Some data\n").is_ok());
+	let mut entry = writer
+		.create_entry(
+			"1.txt",
+			EntryOptions::new().compression(Compression::Deflate),
+		)
+		.unwrap();
+	assert!(entry.write_all(b"Some data\n").is_ok());
+	assert!(entry.finish().is_ok());
 	assert!(writer.finish().is_ok());
 	#[cfg(feature = "crc")]
 	assert_eq!(data, ONE_COMPRESSED_ENTRY);
@@ -111,8 +218,10 @@ fn one_compressed_entry() {
 fn one_uncompressed_entry() {
 	let mut data = Vec::new();
 	let mut writer = Zip::new(&mut data);
-	assert!(writer.create_entry("1.txt", Compression::None, DateTime::default()).is_ok());
-	assert!(writer.write_all(b"Some data\n").is_ok());
+	let mut entry =
+		writer.create_entry("1.txt", EntryOptions::new().compression(Compression::None)).unwrap();
+	assert!(entry.write_all(b"Some data\n").is_ok());
+	assert!(entry.finish().is_ok());
 	assert!(writer.finish().is_ok());
 	#[cfg(feature = "crc")]
 	assert_eq!(data, ONE_UNCOMPRESSED_ENTRY);
@@ -124,10 +233,161 @@ fn one_uncompressed_entry() {
 fn two_uncompressed_entries() {
 	let mut data = Vec::new();
 	let mut writer = Zip::new(&mut data);
-	assert!(writer.create_entry("1.txt", Compression::None, DateTime::default()).is_ok());
-	assert!(writer.write_all(b"Some data\n").is_ok());
-	assert!(writer.create_entry("2.txt", Compression::None, DateTime::default()).is_ok());
-	assert!(writer.write_all(b"Some more data\n").is_ok());
+	let mut entry =
+		writer.create_entry("1.txt", EntryOptions::new().compression(Compression::None)).unwrap();
+	assert!(entry.write_all(b"Some data\n").is_ok());
+	assert!(entry.finish().is_ok());
+	let mut entry =
+		writer.create_entry("2.txt", EntryOptions::new().compression(Compression::None)).unwrap();
+	assert!(entry.write_all(b"Some more data\n").is_ok());
+	assert!(entry.finish().is_ok());
+	assert!(writer.finish().is_ok());
+	#[cfg(feature = "crc")]
+	assert_eq!(data, TWO_ENTRIES);
+	#[cfg(not(feature = "crc"))]
+	assert_eq!(data, two_entries_no_crc!());
+}
+
+#[test]
+#[cfg(feature = "aes")]
+fn one_encrypted_entry() {
+	let mut data = Vec::new();
+	let mut writer = Zip::new(&mut data);
+	let encryption = Encryption::Aes {
+		password: "password".into(),
+		strength: AesStrength::Aes256,
+		version: AesVersion::Ae2,
+	};
+	let mut entry = writer
+		.create_entry(
+			"1.txt",
+			EntryOptions::new().compression(Compression::None).encryption(encryption),
+		)
+		.unwrap();
+	assert!(entry.write_all(b"Some data\n").is_ok());
+	assert!(entry.finish().is_ok());
+	assert!(writer.finish().is_ok());
+	assert_eq!(&data[..46], ONE_ENCRYPTED_ENTRY_HEADER);
+	assert_eq!(decrypt_aes256("password", &data[46..84]), b"Some data\n");
+	assert_eq!(&data[84..88], DATA_DESCRIPTOR);
+	assert_eq!(
+		&data[88..100],
+		&[0x00, 0x00, 0x00, 0x00, 0x26, 0x00, 0x00, 0x00, 0x0A, 0x00, 0x00, 0x00]
+	);
+	assert_eq!(&data[100..104], &[0x50, 0x4B, 0x01, 0x02]);
+	assert_eq!(&data[151..162], &ONE_ENCRYPTED_ENTRY_HEADER[35..]);
+}
+
+#[test]
+#[cfg(feature = "aes")]
+fn encrypted_entry_retry() {
+	let mut writer = Zip::new(FailingWriter {
+		remaining: 46,
+		timeout: true,
+	});
+	let encryption = Encryption::Aes {
+		password: "password".into(),
+		strength: AesStrength::Aes256,
+		version: AesVersion::Ae2,
+	};
+	let options = EntryOptions::new().compression(Compression::None).encryption(encryption);
+	let mut entry = writer.create_entry("1.txt", options).unwrap();
+	assert!(matches!(entry.write(b"hello"), Err(error) if error.kind() == ErrorKind::TimedOut));
+	assert!(entry.write_all(b"hello").is_ok());
+	let entry = entry.finish().unwrap();
+	assert_eq!(entry.compressed_size(), 33);
+	assert_eq!(entry.uncompressed_size(), 5);
+}
+
+#[test]
+#[cfg(feature = "aes")]
+#[cfg(feature = "crc")]
+#[cfg(feature = "deflate")]
+fn one_compressed_encrypted_entry() {
+	let mut data = Vec::new();
+	let mut writer = Zip::new(&mut data);
+	let encryption = Encryption::Aes {
+		password: "password".into(),
+		strength: AesStrength::Aes256,
+		version: AesVersion::Ae1,
+	};
+	let options = EntryOptions::new().compression(Compression::Deflate).encryption(encryption);
+	let mut entry = writer.create_entry("1.txt", options).unwrap();
+	assert!(entry.write_all(b"Some data\n").is_ok());
+	assert!(entry.finish().is_ok());
+	assert!(writer.finish().is_ok());
+	assert_eq!(&data[39..41], &[0x01, 0x00]);
+	assert_eq!(&data[44..46], &[0x08, 0x00]);
+	let end = data.len() - 22 - 62 - 16;
+	let compressed = decrypt_aes256("password", &data[46..end]);
+	let mut decompressed = Vec::new();
+	assert!(flate2::write::DeflateDecoder::new(&mut decompressed).write_all(&compressed).is_ok());
+	assert_eq!(decompressed, b"Some data\n");
+	assert_eq!(
+		&data[end..end + 8],
+		&[0x50, 0x4B, 0x07, 0x08, 0xC9, 0xFA, 0x5C, 0x87]
+	);
+}
+
+#[test]
+fn summary() {
+	let mut writer = Zip::new(Vec::new());
+	let mut entry =
+		writer.create_entry("1.txt", EntryOptions::new().compression(Compression::None)).unwrap();
+	assert!(entry.write_all(b"Some data\n").is_ok());
+	assert!(entry.finish().is_ok());
+	let mut entry =
+		writer.create_entry("2.txt", EntryOptions::new().compression(Compression::None)).unwrap();
+	assert!(entry.write_all(b"Some more data\n").is_ok());
+	assert!(entry.finish().is_ok());
+	let (data, summary) = writer.finish().unwrap();
+	#[cfg(feature = "crc")]
+	assert_eq!(data, TWO_ENTRIES);
+	assert_eq!(summary.size, data.len() as u64);
+	assert_eq!(summary.central_directory_offset, 0x7F);
+	assert_eq!(summary.entries.len(), 2);
+	assert_eq!(summary.entries[1].name(), "2.txt");
+	#[cfg(feature = "crc")]
+	assert_eq!(summary.entries[1].crc(), 0x5ABB9B2F);
+	assert_eq!(summary.entries[1].offset(), 0x3D);
+	assert_eq!(summary.entries[1].compressed_size(), 15);
+	assert_eq!(summary.entries[1].uncompressed_size(), 15);
+}
+
+#[test]
+fn entries() {
+	let mut data = Vec::new();
+	let mut writer = Zip::new(&mut data);
+	assert!(writer.entries().is_empty());
+	let mut entry =
+		writer.create_entry("1.txt", EntryOptions::new().compression(Compression::None)).unwrap();
+	assert!(entry.write_all(b"Some data\n").is_ok());
+	drop(entry);
+	assert_eq!(writer.entries().len(), 1);
+	assert_eq!(writer.entries()[0].name(), "1.txt");
+	#[cfg(feature = "crc")]
+	assert_eq!(writer.entries()[0].crc(), 0x875CFAC9);
+	assert_eq!(writer.entries()[0].offset(), 0);
+	assert_eq!(writer.entries()[0].compressed_size(), 10);
+	assert_eq!(writer.entries()[0].uncompressed_size(), 10);
+}
+
+#[test]
+fn finish_entry() {
+	let mut data = Vec::new();
+	let mut writer = Zip::new(&mut data);
+	assert!(matches!(writer.finish_entry(), Err(Error::NoEntry)));
+	let mut entry =
+		writer.create_entry("1.txt", EntryOptions::new().compression(Compression::None)).unwrap();
+	assert!(entry.write_all(b"Some data\n").is_ok());
+	let entry = entry.finish().unwrap();
+	assert_eq!(entry.name(), "1.txt");
+	assert_eq!(entry.uncompressed_size(), 10);
+	assert!(matches!(writer.finish_entry(), Err(Error::NoEntry)));
+	let mut entry =
+		writer.create_entry("2.txt", EntryOptions::new().compression(Compression::None)).unwrap();
+	assert!(entry.write_all(b"Some more data\n").is_ok());
+	assert!(entry.finish().is_ok());
 	assert!(writer.finish().is_ok());
 	#[cfg(feature = "crc")]
 	assert_eq!(data, TWO_ENTRIES);
@@ -135,11 +395,896 @@ fn two_uncompressed_entries() {
 	assert_eq!(data, two_entries_no_crc!());
 }
 
+#[test]
+fn descriptor_signature() {
+	let mut writer = Zip::new(Vec::new()).descriptor_signature(false);
+	let mut entry =
+		writer.create_entry("1.txt", EntryOptions::new().compression(Compression::None)).unwrap();
+	assert!(entry.write_all(b"Some data\n").is_ok());
+	assert!(entry.finish().is_ok());
+	let (data, summary) = writer.finish().unwrap();
+	assert!(!data.windows(4).any(|window| window == DATA_DESCRIPTOR));
+	assert_eq!(summary.central_directory_offset, 0x39);
+	assert_eq!(summary.size, data.len() as u64);
+	assert_eq!(&data[0x39..0x3D], &[0x50, 0x4B, 0x01, 0x02]);
+}
+
+#[test]
+fn version_needed() {
+	let mut writer = Zip::new(Vec::new());
+	let options = EntryOptions::new().compression(Compression::None);
+	assert!(writer.create_entry("1.txt", options.clone()).unwrap().finish().is_ok());
+	assert!(writer.create_entry("a/", options).unwrap().finish().is_ok());
+	#[cfg(feature = "deflate")]
+	{
+		let options = EntryOptions::new().compression(Compression::Deflate);
+		assert!(writer.create_entry("2.txt", options).unwrap().finish().is_ok());
+	}
+	#[cfg(feature = "aes")]
+	{
+		let encryption = Encryption::Aes {
+			password: "password".into(),
+			strength: AesStrength::Aes256,
+			version: AesVersion::Ae2,
+		};
+		let options = EntryOptions::new().encryption(encryption);
+		assert!(writer.create_entry("3.txt", options).unwrap().finish().is_ok());
+	}
+	let (_, summary) = writer.finish().unwrap();
+	let versions = summary.entries.iter().map(|entry| entry.version_needed()).collect::<Vec<_>>();
+	assert_eq!(versions[..2], [10, 20]);
+	#[cfg(feature = "deflate")]
+	assert_eq!(versions[2], 20);
+	#[cfg(feature = "aes")]
+	assert_eq!(versions[versions.len() - 1], 51);
+}
+
+#[test]
+fn entry_comment() {
+	let mut writer = Zip::new(Vec::new());
+	let options = EntryOptions::new().compression(Compression::None).comment("A comment");
+	let mut entry = writer.create_entry("1.txt", options).unwrap();
+	assert!(entry.write_all(b"Some data\n").is_ok());
+	assert!(entry.finish().is_ok());
+	let (data, summary) = writer.finish().unwrap();
+	assert_eq!(summary.entries[0].comment(), "A comment");
+	assert_eq!(&data[0x3D + 32..0x3D + 34], &[0x09, 0x00]);
+	assert_eq!(&data[data.len() - 31..data.len() - 22], b"A comment");
+	assert_eq!(
+		&data[data.len() - 10..data.len() - 6],
+		&[0x3C, 0x00, 0x00, 0x00]
+	);
+}
+
+#[test]
+#[cfg(feature = "deflate")]
+fn compression_level() {
+	let mut writer = Zip::new(Vec::new());
+	let options = EntryOptions::new().compression(Compression::Deflate).level(0);
+	let mut entry = writer.create_entry("1.txt", options).unwrap();
+	assert!(entry.write_all(b"Some data\n").is_ok());
+	let entry = entry.finish().unwrap();
+	assert!(entry.compressed_size() > entry.uncompressed_size());
+}
+
+#[test]
+fn name_too_long() {
+	let mut data = Vec::new();
+	let mut writer = Zip::new(&mut data);
+	let name = "a".repeat(u16::MAX as usize + 1);
+	assert!(matches!(
+		writer.create_entry(name, EntryOptions::new().compression(Compression::None)),
+		Err(Error::NameTooLong(65536))
+	));
+}
+
+#[test]
+fn poisoned() {
+	let mut writer = Zip::new(FailingWriter {
+		remaining: 45,
+		timeout: false,
+	});
+	let mut entry =
+		writer.create_entry("1.txt", EntryOptions::new().compression(Compression::None)).unwrap();
+	assert!(entry.write_all(b"Some data\n").is_ok());
+	assert!(matches!(
+		entry.finish(),
+		Err(Error::Io(error)) if error.kind() == ErrorKind::BrokenPipe
+	));
+	assert!(matches!(
+		writer.create_entry("2.txt", EntryOptions::new().compression(Compression::None)),
+		Err(Error::Poisoned)
+	));
+	assert!(matches!(writer.finish_entry(), Err(Error::Poisoned)));
+	assert!(matches!(writer.finish(), Err(Error::Poisoned)));
+}
+
+#[test]
+fn date_time() {
+	assert_eq!(
+		DateTime::new(1980, 1, 1, 0, 0, 0).unwrap().to_le_bytes(),
+		[0x00, 0x00, 0x21, 0x00]
+	);
+	assert_eq!(
+		DateTime::new(2024, 2, 29, 13, 45, 31).unwrap().to_le_bytes(),
+		[0xAF, 0x6D, 0x5D, 0x58]
+	);
+	assert!(matches!(
+		DateTime::new(1979, 12, 31, 0, 0, 0),
+		Err(Error::InvalidDate)
+	));
+	assert!(matches!(
+		DateTime::new(2023, 2, 29, 0, 0, 0),
+		Err(Error::InvalidDate)
+	));
+	assert!(matches!(
+		DateTime::new(2024, 1, 1, 24, 0, 0),
+		Err(Error::InvalidDate)
+	));
+}
+
+#[test]
+fn date_time_from_system_time() {
+	assert_eq!(
+		DateTime::from(UNIX_EPOCH + Duration::from_secs(1709214331)),
+		DateTime::new(2024, 2, 29, 13, 45, 31).unwrap()
+	);
+	assert_eq!(
+		DateTime::from(UNIX_EPOCH),
+		DateTime::new(1980, 1, 1, 0, 0, 0).unwrap()
+	);
+	assert_eq!(
+		DateTime::from(UNIX_EPOCH + Duration::from_secs(5000000000)),
+		DateTime::new(2107, 12, 31, 23, 59, 58).unwrap()
+	);
+}
+
+#[test]
+fn source_date_epoch() {
+	std::env::set_var("SOURCE_DATE_EPOCH", "1709214331");
+	assert_eq!(
+		DateTime::source_date_epoch(),
+		DateTime::new(2024, 2, 29, 13, 45, 31).unwrap()
+	);
+	std::env::set_var("SOURCE_DATE_EPOCH", u64::MAX.to_string());
+	assert_eq!(
+		DateTime::source_date_epoch(),
+		DateTime::new(2107, 12, 31, 23, 59, 58).unwrap()
+	);
+	std::env::remove_var("SOURCE_DATE_EPOCH");
+	assert_eq!(
+		DateTime::source_date_epoch(),
+		DateTime::new(1980, 1, 1, 0, 0, 0).unwrap()
+	);
+}
+
+#[test]
+#[cfg(unix)]
+fn add_file() {
+	let path = env::temp_dir().join("zip-writer-add-file.txt");
+	fs::write(&path, b"Some data\n").unwrap();
+	fs::set_permissions(&path, Permissions::from_mode(0o640)).unwrap();
+	File::options()
+		.write(true)
+		.open(&path)
+		.unwrap()
+		.set_modified(UNIX_EPOCH + Duration::from_secs(1709214331))
+		.unwrap();
+	let mut data = Vec::new();
+	let mut writer = Zip::new(&mut data);
+	let entry = writer
+		.add_file(
+			&path,
+			"1.txt",
+			EntryOptions::new().compression(Compression::None),
+		)
+		.unwrap();
+	fs::remove_file(&path).unwrap();
+	assert_eq!(entry.name(), "1.txt");
+	assert_eq!(entry.uncompressed_size(), 10);
+	assert_eq!(entry.permissions(), Some(0o100640));
+	assert_eq!(
+		entry.date_time(),
+		&DateTime::new(2024, 2, 29, 13, 45, 31).unwrap()
+	);
+	let (_, summary) = writer.finish().unwrap();
+	let position = summary.central_directory_offset as usize;
+	assert_eq!(data[position + 4..position + 6], [0x3F, 0x03]);
+	assert_eq!(data[position + 38..position + 42], [0x00, 0x00, 0xA0, 0x81]);
+	assert!(matches!(
+		Zip::new(Vec::new()).add_file(&path, "1.txt", EntryOptions::new()),
+		Err(Error::Io(error)) if error.kind() == ErrorKind::NotFound
+	));
+}
+
+#[test]
+fn glob() {
+	assert!(walk::matches("*.txt", "a/b/1.txt"));
+	assert!(!walk::matches("*.txt", "a/b/1.tmp"));
+	assert!(walk::matches("a/*.txt", "a/1.txt"));
+	assert!(!walk::matches("a/*.txt", "a/b/1.txt"));
+	assert!(walk::matches("a/**/*.txt", "a/1.txt"));
+	assert!(walk::matches("a/**/*.txt", "a/b/c/1.txt"));
+	assert!(walk::matches("**/b", "a/b"));
+	assert!(walk::matches("a/**", "a/b/c"));
+	assert!(walk::matches("?.txt", "1.txt"));
+	assert!(!walk::matches("?.txt", "10.txt"));
+}
+
+#[cfg(unix)]
+fn create_tree(name: &str) -> PathBuf {
+	let root = env::temp_dir().join(name);
+	_ = fs::remove_dir_all(&root);
+	fs::create_dir_all(root.join("b/c")).unwrap();
+	fs::write(root.join("a.txt"), b"Some data\n").unwrap();
+	fs::write(root.join("b/d.txt"), b"Some more data\n").unwrap();
+	fs::write(root.join("b/e.tmp"), b"Temporary\n").unwrap();
+	symlink("a.txt", root.join("f.txt")).unwrap();
+	symlink("b", root.join("g")).unwrap();
+	root
+}
+
+#[test]
+#[cfg(unix)]
+fn add_dir_all() {
+	let root = create_tree("zip-writer-add-dir-all");
+	let mut writer = Zip::new(Vec::new());
+	let filter = Filter::new().exclude("*.tmp");
+	assert!(writer.add_dir_all(&root, "out", &filter, EntryOptions::new()).is_ok());
+	let names = writer.entries().iter().map(|entry| entry.name()).collect::<Vec<_>>();
+	assert_eq!(
+		names,
+		[
+			"out/a.txt",
+			"out/b/",
+			"out/b/c/",
+			"out/b/d.txt",
+			"out/f.txt",
+			"out/g"
+		]
+	);
+	let link = &writer.entries()[4];
+	assert_eq!(
+		link.permissions().map(|mode| mode & 0o170000),
+		Some(0o120000)
+	);
+	assert_eq!(link.uncompressed_size(), 5);
+	assert_eq!(
+		writer.entries()[1].permissions().map(|mode| mode & 0o170000),
+		Some(0o040000)
+	);
+	assert!(writer.finish().is_ok());
+
+	let mut writer = Zip::new(Vec::new());
+	let filter = Filter::new().include("**/*.txt").follow_symlinks(true);
+	assert!(writer.add_dir_all(&root, "", &filter, EntryOptions::new()).is_ok());
+	let names = writer.entries().iter().map(|entry| entry.name()).collect::<Vec<_>>();
+	assert_eq!(names, ["a.txt", "b/", "b/c/", "b/d.txt", "f.txt"]);
+	assert_eq!(writer.entries()[4].uncompressed_size(), 10);
+	fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+#[cfg(unix)]
+fn add_dir_all_links() {
+	let root = create_tree("zip-writer-add-dir-all-links");
+	symlink("../a.txt", root.join("b/h")).unwrap();
+	for policy in [NamePolicy::Reject, NamePolicy::Normalize] {
+		let mut writer = Zip::new(Cursor::new(Vec::new())).name_policy(policy);
+		assert!(writer.add_dir_all(&root, "out", &Filter::new(), EntryOptions::new()).is_ok());
+		let (data, _) = writer.finish().unwrap();
+		let mut target = String::new();
+		let mut reader = read::Zip::new(data).unwrap();
+		assert!(reader.by_name("out/b/h").unwrap().read_to_string(&mut target).is_ok());
+		assert_eq!(target, "../a.txt");
+	}
+
+	symlink("../outside", root.join("h")).unwrap();
+	for policy in [NamePolicy::Reject, NamePolicy::Normalize] {
+		let mut writer = Zip::new(Vec::new()).name_policy(policy);
+		let result = writer.add_dir_all(&root, "", &Filter::new(), EntryOptions::new());
+		assert!(matches!(result, Err(Error::InvalidName(name)) if name == "../outside"));
+	}
+
+	let mut writer = Zip::new(Vec::new()).name_policy(NamePolicy::Allow);
+	#[cfg(not(feature = "aes"))]
+	let options = EntryOptions::new();
+	#[cfg(feature = "aes")]
+	let options = EntryOptions::new().encryption(Encryption::Aes {
+		password: "password".into(),
+		strength: AesStrength::Aes256,
+		version: AesVersion::Ae2,
+	});
+	assert!(writer.add_dir_all(&root, "", &Filter::new(), options).is_ok());
+	fs::remove_dir_all(&root).unwrap();
+	let link = writer.entries().iter().find(|entry| entry.name() == "h").unwrap();
+	assert_eq!(link.method(), 0);
+	assert_eq!(link.uncompressed_size(), 10);
+}
+
+#[test]
+#[cfg(unix)]
+fn reproducible() {
+	let archive = |name: &str, seconds: u64, mode: u32| {
+		let root = create_tree(name);
+		fs::set_permissions(root.join("a.txt"), Permissions::from_mode(mode)).unwrap();
+		File::options()
+			.write(true)
+			.open(root.join("b/d.txt"))
+			.unwrap()
+			.set_modified(UNIX_EPOCH + Duration::from_secs(seconds))
+			.unwrap();
+		let epoch = DateTime::new(2024, 1, 1, 0, 0, 0).unwrap();
+		let mut writer = Zip::new(Vec::new()).reproducible_at(epoch);
+		assert!(writer.add_dir_all(&root, "", &Filter::new(), EntryOptions::new()).is_ok());
+		fs::remove_dir_all(&root).unwrap();
+		let (data, summary) = writer.finish().unwrap();
+		assert!(summary.entries.iter().all(|entry| entry.date_time() <= &epoch));
+		assert_eq!(summary.entries[0].permissions(), Some(0o100644));
+		data
+	};
+	assert_eq!(
+		archive("zip-writer-reproducible-1", 1709214331, 0o600),
+		archive("zip-writer-reproducible-2", 1800000000, 0o640)
+	);
+}
+
+#[test]
+fn name_policy() {
+	let mut writer = Zip::new(Vec::new());
+	for name in [
+		"",
+		"a\\b.txt",
+		"/a.txt",
+		"C:a.txt",
+		"a/../../b.txt",
+		"a\0.txt",
+	] {
+		assert!(matches!(
+			writer.create_entry(name, EntryOptions::new()),
+			Err(Error::InvalidName(invalid)) if invalid == name
+		));
+	}
+	assert!(writer.create_entry("a/b..txt", EntryOptions::new()).is_ok());
+
+	let mut writer = Zip::new(Vec::new()).name_policy(NamePolicy::Normalize);
+	for (name, normalized) in [
+		("a\\b.txt", "a/b.txt"),
+		("/a.txt", "a.txt"),
+		("C:\\a\\b/", "a/b/"),
+		("./a/../../b.txt", "b.txt"),
+	] {
+		assert_eq!(
+			writer.create_entry(name, EntryOptions::new()).unwrap().finish().unwrap().name(),
+			normalized
+		);
+	}
+	assert!(matches!(
+		writer.create_entry("../", EntryOptions::new()),
+		Err(Error::InvalidName(_))
+	));
+
+	let mut writer = Zip::new(Vec::new()).name_policy(NamePolicy::Allow);
+	assert_eq!(
+		writer.create_entry("../a.txt", EntryOptions::new()).unwrap().finish().unwrap().name(),
+		"../a.txt"
+	);
+}
+
+#[test]
+fn duplicates() {
+	let mut writer = Zip::new(Vec::new());
+	assert!(writer.create_entry("a.txt", EntryOptions::new()).is_ok());
+	assert!(writer.create_entry("a.txt", EntryOptions::new()).is_ok());
+
+	let mut writer = Zip::new(Vec::new()).duplicates(Duplicates::Reject, false);
+	assert!(writer.create_entry("a.txt", EntryOptions::new()).is_ok());
+	assert!(matches!(
+		writer.create_entry("a.txt", EntryOptions::new()),
+		Err(Error::DuplicateName(name)) if name == "a.txt"
+	));
+	assert!(writer.create_entry("A.txt", EntryOptions::new()).is_ok());
+
+	let mut writer = Zip::new(Vec::new()).duplicates(Duplicates::Reject, true);
+	assert!(writer.create_entry("a.txt", EntryOptions::new()).is_ok());
+	assert!(matches!(
+		writer.create_entry("A.TXT", EntryOptions::new()),
+		Err(Error::DuplicateName(_))
+	));
+
+	let mut writer = Zip::new(Vec::new()).duplicates(Duplicates::Rename, true);
+	for name in [
+		"a.txt",
+		"A.txt",
+		"a (1).txt",
+		"b.tar.gz",
+		"b.tar.gz",
+		"c/",
+		"c/",
+		".d",
+		".d",
+	] {
+		assert!(writer.create_entry(name, EntryOptions::new()).is_ok());
+	}
+	let names = writer.entries().iter().map(|entry| entry.name()).collect::<Vec<_>>();
+	assert_eq!(
+		names,
+		[
+			"a.txt",
+			"A (1).txt",
+			"a (1) (1).txt",
+			"b.tar.gz",
+			"b.tar (1).gz",
+			"c/",
+			"c (1)/",
+			".d",
+			".d (1)",
+		]
+	);
+}
+
+#[test]
+fn cp437() {
+	let mut writer = Zip::new(Vec::new());
+	let options = EntryOptions::new().compression(Compression::None).cp437(true);
+	assert!(writer.create_entry("café.txt", options.clone()).is_ok());
+	assert!(writer.create_entry("日本.txt", options.comment("☃")).is_ok());
+	let (data, summary) = writer.finish().unwrap();
+	assert_eq!(data[6..8], [0b00001000, 0b00000000]);
+	assert_eq!(data[28..30], [0x00, 0x00]);
+	assert_eq!(data[30..38], *b"caf\x82.txt");
+	let position = summary.entries[1].offset() as usize;
+	assert_eq!(data[position + 26..position + 30], [0x06, 0x00, 0x13, 0x00]);
+	assert_eq!(data[position + 30..position + 36], *b"__.txt");
+	assert_eq!(data[position + 36..position + 40], [0x75, 0x70, 0x0F, 0x00]);
+	assert_eq!(data[position + 45..position + 55], *"日本.txt".as_bytes());
+	assert_eq!(summary.entries[1].name(), "日本.txt");
+	assert_eq!(summary.entries[1].comment(), "☃");
+	let position = summary.central_directory_offset as usize + 46 + 8;
+	let position = position + 46 + 6;
+	assert_eq!(data[position..position + 2], [0x75, 0x70]);
+	assert_eq!(data[position + 19..position + 21], [0x75, 0x63]);
+	assert_eq!(data[position + 31..position + 32], *b"_");
+
+	let mut writer = Zip::new(Vec::new());
+	assert!(matches!(
+		writer.create_entry("€".repeat(21844), EntryOptions::new().cp437(true)),
+		Err(Error::ExtraFieldTooLong(65541))
+	));
+	let options = EntryOptions::new().cp437(true).comment("€".repeat(21844));
+	assert!(matches!(
+		writer.create_entry("1.txt", options),
+		Err(Error::ExtraFieldTooLong(65541))
+	));
+}
+
+#[test]
+fn raw_name() {
+	let mut writer = Zip::new(Vec::new()).duplicates(Duplicates::Rename, false);
+	let options = EntryOptions::new().compression(Compression::None).comment("é");
+	let entry = writer.create_entry_raw(b"\x93\xFA\x96\x7B.txt", false, options).unwrap();
+	let entry = entry.finish().unwrap();
+	assert_eq!(entry.name_bytes(), b"\x93\xFA\x96\x7B.txt");
+	assert_eq!(entry.name(), "ô·û{.txt");
+	assert!(matches!(
+		writer.create_entry_raw(b"\x93\xFA\x96\x7B.txt", false, EntryOptions::new()),
+		Err(Error::DuplicateName(_))
+	));
+	assert!(matches!(
+		writer.create_entry_raw(b"../a.txt", true, EntryOptions::new()),
+		Err(Error::InvalidName(_))
+	));
+	assert!(matches!(
+		writer.create_entry_raw(b"\xFF\xFE.txt", true, EntryOptions::new()),
+		Err(Error::InvalidName(_))
+	));
+	let (data, summary) = writer.finish().unwrap();
+	assert_eq!(data[6..8], [0b00001000, 0b00000000]);
+	assert_eq!(data[30..38], *b"\x93\xFA\x96\x7B.txt");
+	let position = summary.central_directory_offset as usize;
+	assert_eq!(
+		data[position + 46..position + 55],
+		*b"\x93\xFA\x96\x7B.txt\x82"
+	);
+
+	let mut writer = Zip::new(Vec::new()).name_policy(NamePolicy::Normalize);
+	assert!(matches!(
+		writer.create_entry_raw(b"a\\b.txt", true, EntryOptions::new()),
+		Err(Error::InvalidName(_))
+	));
+	let entry = writer.create_entry_raw("é.txt", true, EntryOptions::new()).unwrap();
+	assert_eq!(entry.finish().unwrap().name(), "é.txt");
+}
+
+#[test]
+fn extra_fields() {
+	let mut writer = Zip::new(Vec::new());
+	let options = EntryOptions::new()
+		.compression(Compression::None)
+		.extra_field(0xCAFE, [0x01, 0x02])
+		.local_extra_field(0xBEEF, [0x03])
+		.central_extra_field(0xF00D, []);
+	assert!(writer.create_entry("1.txt", options).is_ok());
+	let (data, summary) = writer.finish().unwrap();
+	assert_eq!(data[28..30], [0x0B, 0x00]);
+	assert_eq!(
+		data[35..46],
+		[0xFE, 0xCA, 0x02, 0x00, 0x01, 0x02, 0xEF, 0xBE, 0x01, 0x00, 0x03]
+	);
+	let position = summary.central_directory_offset as usize;
+	assert_eq!(data[position + 30..position + 32], [0x0A, 0x00]);
+	assert_eq!(
+		data[position + 51..position + 61],
+		[0xFE, 0xCA, 0x02, 0x00, 0x01, 0x02, 0x0D, 0xF0, 0x00, 0x00]
+	);
+
+	let mut writer = Zip::new(Vec::new());
+	assert!(matches!(
+		writer.create_entry("1.txt", EntryOptions::new().extra_field(0x0001, [])),
+		Err(Error::ReservedExtraField(0x0001))
+	));
+	let options = EntryOptions::new().local_extra_field(0xCAFE, vec![0; 40000]);
+	assert!(matches!(
+		writer.create_entry("1.txt", options.local_extra_field(0xCAFE, vec![0; 40000])),
+		Err(Error::ExtraFieldTooLong(80008))
+	));
+	assert!(matches!(
+		writer.create_entry(
+			"1.txt",
+			EntryOptions::new().central_extra_field(0xCAFE, vec![0; 65532])
+		),
+		Err(Error::ExtraFieldTooLong(65536))
+	));
+}
+
+#[test]
+fn owner() {
+	let mut writer = Zip::new(Vec::new());
+	let options = EntryOptions::new().compression(Compression::None).owner(1000, 0);
+	assert!(writer.create_entry("1.txt", options).is_ok());
+	let options = EntryOptions::new().compression(Compression::None).owner(0x12345678, 70000);
+	assert!(writer.create_entry("2.txt", options).is_ok());
+	let (data, summary) = writer.finish().unwrap();
+	let field = [0x75, 0x78, 0x06, 0x00, 0x01, 0x02, 0xE8, 0x03, 0x01, 0x00];
+	assert_eq!(data[28..30], [0x0A, 0x00]);
+	assert_eq!(data[35..45], field);
+	let position = summary.entries[1].offset() as usize;
+	assert_eq!(
+		data[position + 35..position + 46],
+		[0x75, 0x78, 0x0A, 0x00, 0x01, 0x04, 0x78, 0x56, 0x34, 0x12, 0x03]
+	);
+	let position = summary.central_directory_offset as usize;
+	assert_eq!(data[position + 51..position + 61], field);
+}
+
+#[test]
+fn ntfs() {
+	assert_eq!(
+		SystemTime::from(DateTime::new(2024, 2, 29, 13, 45, 30).unwrap()),
+		UNIX_EPOCH + Duration::from_secs(1709214330)
+	);
+	let mut writer = Zip::new(Vec::new());
+	let modified = UNIX_EPOCH + Duration::new(1709214331, 500);
+	let options = EntryOptions::new().compression(Compression::None).ntfs_times(
+		modified,
+		UNIX_EPOCH,
+		UNIX_EPOCH - Duration::from_secs(1),
+	);
+	assert!(writer.create_entry("1.txt", options).is_ok());
+	let date_time = DateTime::new(2024, 2, 29, 13, 45, 30).unwrap();
+	let options =
+		EntryOptions::new().compression(Compression::None).date_time(date_time).ntfs(true);
+	assert!(writer.create_entry("2.txt", options).is_ok());
+	let (data, summary) = writer.finish().unwrap();
+	assert_eq!(summary.entries[0].date_time(), &date_time);
+	assert_eq!(data[28..30], [0x24, 0x00]);
+	assert_eq!(
+		data[35..47],
+		[0x0A, 0x00, 0x20, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x18, 0x00]
+	);
+	assert_eq!(data[47..55], 133536879310000005u64.to_le_bytes());
+	assert_eq!(data[55..63], 116444736000000000u64.to_le_bytes());
+	assert_eq!(data[63..71], 116444735990000000u64.to_le_bytes());
+	let position = summary.entries[1].offset() as usize + 47;
+	assert_eq!(
+		data[position..position + 8],
+		133536879300000000u64.to_le_bytes()
+	);
+	let position = summary.central_directory_offset as usize + 30;
+	assert_eq!(data[position..position + 2], [0x24, 0x00]);
+
+	let mut writer = Zip::new(Vec::new()).reproducible_at(date_time);
+	assert!(writer.create_entry("1.txt", EntryOptions::new().ntfs(true)).is_ok());
+	let (data, _) = writer.finish().unwrap();
+	assert_eq!(data[28..30], [0x00, 0x00]);
+}
+
+#[test]
+fn append() {
+	let mut writer = Zip::append(Cursor::new(ONE_UNCOMPRESSED_ENTRY.to_vec()))
+		.unwrap()
+		.duplicates(Duplicates::Reject, false);
+	assert_eq!(writer.entries().len(), 1);
+	assert_eq!(writer.entries()[0].name(), "1.txt");
+	assert!(matches!(
+		writer.create_entry("1.txt", EntryOptions::new()),
+		Err(Error::DuplicateName(_))
+	));
+	let mut entry =
+		writer.create_entry("2.txt", EntryOptions::new().compression(Compression::None)).unwrap();
+	assert!(entry.write_all(b"Some more data\n").is_ok());
+	assert!(entry.finish().is_ok());
+	let (data, summary) = writer.finish().unwrap();
+	assert_eq!(summary.entries.len(), 2);
+	assert_eq!(summary.size, data.get_ref().len() as u64);
+	#[cfg(feature = "crc")]
+	assert_eq!(data.into_inner(), TWO_ENTRIES);
+
+	let mut archive = ONE_UNCOMPRESSED_ENTRY.to_vec();
+	let length = archive.len();
+	archive[length - 2..].copy_from_slice(&[0x03, 0x00]);
+	archive.extend(b"abc");
+	// Attributes of an entry made on MS-DOS, read-only and archived.
+	archive[0x3D + 4..0x3D + 6].copy_from_slice(&[0x14, 0x00]);
+	archive[0x3D + 36..0x3D + 42].copy_from_slice(&[0x01, 0x00, 0x21, 0x00, 0x00, 0x00]);
+	let (data, _) = Zip::append(Cursor::new(archive.clone())).unwrap().finish().unwrap();
+	assert_eq!(data.into_inner(), archive);
+	assert!(matches!(
+		Zip::append(Cursor::new(b"Some data\n".to_vec())),
+		Err(Error::InvalidArchive(_))
+	));
+}
+
+#[test]
+fn read() {
+	for signature in [true, false] {
+		let mut writer = Zip::new(Cursor::new(Vec::new())).descriptor_signature(signature);
+		let mut entry = writer.create_entry("1.txt", EntryOptions::new()).unwrap();
+		assert!(entry.write_all(&b"Some data\n".repeat(100)).is_ok());
+		assert!(entry.finish().is_ok());
+		let options = EntryOptions::new().compression(Compression::None).comment("A comment");
+		let mut entry = writer.create_entry("2.txt", options).unwrap();
+		assert!(entry.write_all(b"Some more data\n").is_ok());
+		assert!(entry.finish().is_ok());
+		let (data, _) = writer.finish().unwrap();
+		let mut reader = read::Zip::new(data).unwrap();
+		assert_eq!(reader.entries().len(), 2);
+		assert_eq!(reader.entries()[1].comment(), "A comment");
+		let mut content = Vec::new();
+		assert!(reader.entry(0).unwrap().read_to_end(&mut content).is_ok());
+		assert_eq!(content, b"Some data\n".repeat(100));
+		let mut content = String::new();
+		assert!(reader.by_name("2.txt").unwrap().read_to_string(&mut content).is_ok());
+		assert_eq!(content, "Some more data\n");
+		assert!(matches!(
+			reader.by_name("3.txt"),
+			Err(Error::UnknownEntry(_))
+		));
+	}
+
+	#[cfg(feature = "crc")]
+	{
+		let mut data = TWO_ENTRIES.to_vec();
+		data[35] = b's';
+		let mut reader = read::Zip::new(Cursor::new(data)).unwrap();
+		let error = reader.entry(0).unwrap().read_to_end(&mut Vec::new()).unwrap_err();
+		assert!(matches!(Error::from(error), Error::ChecksumMismatch(name) if name == "1.txt"));
+	}
+	assert!(matches!(
+		read::Zip::new(Cursor::new(&TWO_ENTRIES[..100])),
+		Err(Error::InvalidArchive(_))
+	));
+}
+
+#[test]
+fn stream() {
+	for signature in [true, false] {
+		let mut writer = Zip::new(Vec::new()).descriptor_signature(signature);
+		let mut entry = writer.create_entry("1.txt", EntryOptions::new()).unwrap();
+		assert!(entry.write_all(&b"Some data\n".repeat(100)).is_ok());
+		assert!(entry.finish().is_ok());
+		let options = EntryOptions::new().compression(Compression::None);
+		let mut entry = writer.create_entry("2.txt", options.clone()).unwrap();
+		assert!(entry.write_all(b"PK\x07\x08\x00\x00\x00\x00\x04\x00\x00\x00PK\x03\x04").is_ok());
+		assert!(entry.finish().is_ok());
+		assert!(writer.create_entry("3.txt", options).unwrap().finish().is_ok());
+		let (data, summary) = writer.finish().unwrap();
+		let mut reader = read::Stream::new(&data[..]);
+		let mut entry = reader.next_entry().unwrap().unwrap();
+		let mut content = Vec::new();
+		assert!(entry.read_to_end(&mut content).is_ok());
+		assert_eq!(content, b"Some data\n".repeat(100));
+		assert_eq!(entry.entry().name(), "1.txt");
+		assert_eq!(
+			entry.entry().compressed_size(),
+			summary.entries[0].compressed_size()
+		);
+		#[cfg(feature = "crc")]
+		assert_eq!(entry.entry().crc(), summary.entries[0].crc());
+		let mut entry = reader.next_entry().unwrap().unwrap();
+		let mut content = Vec::new();
+		assert!(entry.read_to_end(&mut content).is_ok());
+		assert_eq!(content.len(), 16);
+		assert_eq!(
+			reader.next_entry().unwrap().unwrap().entry().name(),
+			"3.txt"
+		);
+		assert!(reader.next_entry().unwrap().is_none());
+		assert!(reader.next_entry().unwrap().is_none());
+	}
+
+	let mut reader = read::Stream::new(&TWO_ENTRIES[..40]);
+	let mut entry = reader.next_entry().unwrap().unwrap();
+	let error = entry.read_to_end(&mut Vec::new()).unwrap_err();
+	assert!(matches!(Error::from(error), Error::InvalidArchive(_)));
+	#[cfg(feature = "crc")]
+	{
+		let mut data = TWO_ENTRIES.to_vec();
+		data[35] = b's';
+		let mut reader = read::Stream::new(&data[..]);
+		let mut entry = reader.next_entry().unwrap().unwrap();
+		let error = entry.read_to_end(&mut Vec::new()).unwrap_err();
+		assert!(matches!(Error::from(error), Error::InvalidArchive(_)));
+	}
+}
+
+#[test]
+fn verify() {
+	let report = crate::verify(Cursor::new(TWO_ENTRIES)).unwrap();
+	assert!(report.is_valid());
+	assert_eq!(report.entries, 2);
+	assert!(report.unchecked.is_empty());
+
+	let mut data = TWO_ENTRIES.to_vec();
+	data[30] = b'x';
+	data[0x3D + 35] = b's';
+	data[220..224].copy_from_slice(&[0x00, 0x00, 0x00, 0x00]);
+	let report = crate::verify(Cursor::new(data)).unwrap();
+	assert!(!report.is_valid());
+	let problems = report
+		.problems
+		.iter()
+		.map(|problem| (problem.entry.as_str(), problem.error.to_string()))
+		.collect::<Vec<_>>();
+	assert!(problems.contains(&(
+		"1.txt",
+		"invalid archive: local header does not match the central directory".to_string()
+	)));
+	assert!(problems.contains(&(
+		"2.txt",
+		"invalid archive: entry overlaps another entry".to_string()
+	)));
+	#[cfg(feature = "crc")]
+	assert!(report
+		.problems
+		.iter()
+		.any(|problem| matches!(&problem.error, Error::ChecksumMismatch(name) if name == "2.txt")));
+
+	let mut writer = Zip::new(Cursor::new(Vec::new())).name_policy(NamePolicy::Allow);
+	assert!(writer.create_entry("../1.txt", EntryOptions::new()).unwrap().finish().is_ok());
+	assert!(writer.create_entry("2.txt", EntryOptions::new()).unwrap().finish().is_ok());
+	assert!(writer.create_entry("2.txt", EntryOptions::new()).unwrap().finish().is_ok());
+	let (data, _) = writer.finish().unwrap();
+	let report = crate::verify(data).unwrap();
+	assert_eq!(report.problems.len(), 2);
+	assert!(matches!(report.problems[0].error, Error::InvalidName(_)));
+	assert!(matches!(report.problems[1].error, Error::DuplicateName(_)));
+	assert!(matches!(
+		crate::verify(Cursor::new(&TWO_ENTRIES[..100])),
+		Err(Error::InvalidArchive(_))
+	));
+}
+
+#[test]
+fn transform() {
+	let mut writer = Zip::new(Vec::new());
+	assert!(writer.transform(Cursor::new(TWO_ENTRIES), |_| Action::Keep).is_ok());
+	let (data, _) = writer.finish().unwrap();
+	assert_eq!(data, TWO_ENTRIES);
+	let mut archive = ONE_UNCOMPRESSED_ENTRY.to_vec();
+	archive[0x3D + 4..0x3D + 6].copy_from_slice(&[0x14, 0x00]);
+	archive[0x3D + 36..0x3D + 42].copy_from_slice(&[0x01, 0x00, 0x21, 0x00, 0x00, 0x00]);
+	let mut writer = Zip::new(Vec::new());
+	assert!(writer.transform(Cursor::new(&archive), |_| Action::Keep).is_ok());
+	let (data, _) = writer.finish().unwrap();
+	assert_eq!(data, archive);
+
+	let mut writer = Zip::new(Cursor::new(Vec::new()));
+	let options = EntryOptions::new().compression(Compression::None).comment("A comment");
+	let mut entry = writer.create_entry("1.txt", options).unwrap();
+	assert!(entry.write_all(b"Some data\n").is_ok());
+	assert!(entry.finish().is_ok());
+	for name in ["2.txt", "3.txt", "4.txt"] {
+		let mut entry = writer.create_entry(name, EntryOptions::new()).unwrap();
+		assert!(entry.write_all(b"Some more data\n").is_ok());
+		assert!(entry.finish().is_ok());
+	}
+	let (data, _) = writer.finish().unwrap();
+	let mut writer = Zip::new(Cursor::new(Vec::new()));
+	let result = writer.transform(data, |entry| match entry.name() {
+		"1.txt" => Action::Rename("a/1.txt".to_string()),
+		"2.txt" => Action::Drop,
+		"3.txt" => Action::Replace(b"Replaced\n".to_vec(), EntryOptions::new()),
+		_ => Action::Keep,
+	});
+	assert!(result.is_ok());
+	let (data, summary) = writer.finish().unwrap();
+	assert!(crate::verify(Cursor::new(data.get_ref())).unwrap().is_valid());
+	let names = summary.entries.iter().map(|entry| entry.name()).collect::<Vec<_>>();
+	assert_eq!(names, ["a/1.txt", "3.txt", "4.txt"]);
+	assert_eq!(summary.entries[0].comment(), "A comment");
+	let mut reader = read::Zip::new(data).unwrap();
+	let mut content = String::new();
+	assert!(reader.by_name("3.txt").unwrap().read_to_string(&mut content).is_ok());
+	assert_eq!(content, "Replaced\n");
+	let mut content = String::new();
+	assert!(reader.by_name("4.txt").unwrap().read_to_string(&mut content).is_ok());
+	assert_eq!(content, "Some more data\n");
+
+	let copy = |seconds| {
+		let time = UNIX_EPOCH + Duration::from_secs(seconds);
+		let mut writer = Zip::new(Cursor::new(Vec::new()));
+		let options = EntryOptions::new().ntfs_times(time, time, time);
+		let mut entry = writer.create_entry("1.txt", options).unwrap();
+		assert!(entry.write_all(b"Some data\n").is_ok());
+		assert!(entry.finish().is_ok());
+		let (data, _) = writer.finish().unwrap();
+		let epoch = DateTime::new(2024, 1, 1, 0, 0, 0).unwrap();
+		let mut writer = Zip::new(Vec::new()).reproducible_at(epoch);
+		assert!(writer.transform(data, |_| Action::Keep).is_ok());
+		let (data, summary) = writer.finish().unwrap();
+		assert!(crate::extra::find(&summary.entries[0].extra_field, 0x000A).is_none());
+		assert_eq!(&data[28..30], &[0x00, 0x00]);
+		data
+	};
+	assert_eq!(copy(1709214331), copy(1800000000));
+
+	let mut writer = Zip::new(Vec::new()).duplicates(Duplicates::Reject, false);
+	assert!(matches!(
+		writer.transform(Cursor::new(TWO_ENTRIES), |_| Action::Rename(
+			"1.txt".to_string()
+		)),
+		Err(Error::DuplicateName(_))
+	));
+}
+
+#[test]
+fn verify_overlap() {
+	let mut writer = Zip::new(Vec::new());
+	for name in ["a", "b", "c"] {
+		let mut entry =
+			writer.create_entry(name, EntryOptions::new().compression(Compression::None)).unwrap();
+		assert!(entry.write_all(b"Some data\n").is_ok());
+		assert!(entry.finish().is_ok());
+	}
+	let (mut data, summary) = writer.finish().unwrap();
+	let position = summary.central_directory_offset as usize + 20;
+	data[position..position + 4].copy_from_slice(&120u32.to_le_bytes());
+	let report = crate::verify(Cursor::new(data)).unwrap();
+	let overlapping = report
+		.problems
+		.iter()
+		.filter(|problem| problem.error.to_string().ends_with("entry overlaps another entry"))
+		.map(|problem| problem.entry.as_str())
+		.collect::<Vec<_>>();
+	assert_eq!(overlapping, ["b", "c"]);
+}
+
+#[test]
+fn error_conversion() {
+	let error = io::Error::from(Error::InvalidDate);
+	assert_eq!(error.kind(), ErrorKind::InvalidInput);
+	assert!(matches!(Error::from(error), Error::InvalidDate));
+	let error = Error::from(io::Error::from(ErrorKind::BrokenPipe));
+	assert!(matches!(&error, Error::Io(error) if error.kind() == ErrorKind::BrokenPipe));
+	assert_eq!(io::Error::from(error).kind(), ErrorKind::BrokenPipe);
+}
+
 #[tokio::test]
 #[cfg(feature = "tokio")]
 async fn tokio_no_entries() {
 	let mut data = Vec::new();
-	let mut writer = crate::tokio::Zip::new(&mut data);
+	let writer = crate::tokio::Zip::new(&mut data);
 	assert!(writer.finish().await.is_ok());
 	assert_eq!(data, NO_ENTRIES);
 }
@@ -149,8 +1294,12 @@ async fn tokio_no_entries() {
 async fn tokio_one_uncompressed_entry() {
 	let mut data = Vec::new();
 	let mut writer = crate::tokio::Zip::new(&mut data);
-	assert!(writer.create_entry("1.txt", Compression::None, DateTime::default()).await.is_ok());
-	assert!(writer.write_all(b"Some data\n").await.is_ok());
+	let mut entry = writer
+		.create_entry("1.txt", EntryOptions::new().compression(Compression::None))
+		.await
+		.unwrap();
+	assert!(entry.write_all(b"Some data\n").await.is_ok());
+	assert!(entry.finish().await.is_ok());
 	assert!(writer.finish().await.is_ok());
 	#[cfg(feature = "crc")]
 	assert_eq!(data, ONE_UNCOMPRESSED_ENTRY);
@@ -164,8 +1313,15 @@ async fn tokio_one_uncompressed_entry() {
 async fn tokio_one_compressed_entry() {
 	let mut data = Vec::new();
 	let mut writer = crate::tokio::Zip::new(&mut data);
-	assert!(writer.create_entry("1.txt", Compression::Deflate, DateTime::default()).await.is_ok());
-	assert!(writer.write_all(b"Some data\n").await.is_ok());
+	let mut entry = writer
+		.create_entry(
+			"1.txt",
+			EntryOptions::new().compression(Compression::Deflate),
+		)
+		.await
+		.unwrap();
+	assert!(entry.write_all(b"Some data\n").await.is_ok());
+	assert!(entry.finish().await.is_ok());
 	assert!(writer.finish().await.is_ok());
 	#[cfg(feature = "crc")]
 	assert_eq!(data, ONE_COMPRESSED_ENTRY);
@@ -178,13 +1334,258 @@ async fn tokio_one_compressed_entry() {
 async fn tokio_two_uncompressed_entries() {
 	let mut data = Vec::new();
 	let mut writer = crate::tokio::Zip::new(&mut data);
-	assert!(writer.create_entry("1.txt", Compression::None, DateTime::default()).await.is_ok());
-	assert!(writer.write_all(b"Some data\n").await.is_ok());
-	assert!(writer.create_entry("2.txt", Compression::None, DateTime::default()).await.is_ok());
-	assert!(writer.write_all(b"Some more data\n").await.is_ok());
+	let mut entry = writer
+		.create_entry("1.txt", EntryOptions::new().compression(Compression::None))
+		.await
+		.unwrap();
+	assert!(entry.write_all(b"Some data\n").await.is_ok());
+	assert!(entry.finish().await.is_ok());
+	let mut entry = writer
+		.create_entry("2.txt", EntryOptions::new().compression(Compression::None))
+		.await
+		.unwrap();
+	assert!(entry.write_all(b"Some more data\n").await.is_ok());
+	assert!(entry.finish().await.is_ok());
 	assert!(writer.finish().await.is_ok());
 	#[cfg(feature = "crc")]
 	assert_eq!(data, TWO_ENTRIES);
 	#[cfg(not(feature = "crc"))]
 	assert_eq!(data, two_entries_no_crc!());
 }
+
+#[tokio::test]
+#[cfg(feature = "aes")]
+#[cfg(feature = "tokio")]
+async fn tokio_one_encrypted_entry() {
+	let mut data = Vec::new();
+	let mut writer = crate::tokio::Zip::new(&mut data);
+	let encryption = Encryption::Aes {
+		password: "password".into(),
+		strength: AesStrength::Aes256,
+		version: AesVersion::Ae2,
+	};
+	let mut entry = writer
+		.create_entry(
+			"1.txt",
+			EntryOptions::new().compression(Compression::None).encryption(encryption),
+		)
+		.await
+		.unwrap();
+	assert!(entry.write_all(b"Some data\n").await.is_ok());
+	assert!(entry.finish().await.is_ok());
+	assert!(writer.finish().await.is_ok());
+	assert_eq!(&data[..46], ONE_ENCRYPTED_ENTRY_HEADER);
+	assert_eq!(decrypt_aes256("password", &data[46..84]), b"Some data\n");
+	assert_eq!(&data[84..88], DATA_DESCRIPTOR);
+	assert_eq!(
+		&data[88..100],
+		&[0x00, 0x00, 0x00, 0x00, 0x26, 0x00, 0x00, 0x00, 0x0A, 0x00, 0x00, 0x00]
+	);
+	assert_eq!(&data[151..162], &ONE_ENCRYPTED_ENTRY_HEADER[35..]);
+}
+
+#[tokio::test]
+#[cfg(feature = "tokio")]
+async fn tokio_summary() {
+	let mut writer = crate::tokio::Zip::new(Vec::new());
+	let mut entry = writer
+		.create_entry("1.txt", EntryOptions::new().compression(Compression::None))
+		.await
+		.unwrap();
+	assert!(entry.write_all(b"Some data\n").await.is_ok());
+	assert!(entry.finish().await.is_ok());
+	let (data, summary) = writer.finish().await.unwrap();
+	#[cfg(feature = "crc")]
+	assert_eq!(data, ONE_UNCOMPRESSED_ENTRY);
+	assert_eq!(summary.size, data.len() as u64);
+	assert_eq!(summary.central_directory_offset, 0x3D);
+	assert_eq!(summary.entries.len(), 1);
+	assert_eq!(summary.entries[0].name(), "1.txt");
+	assert_eq!(summary.entries[0].uncompressed_size(), 10);
+}
+
+#[tokio::test]
+#[cfg(feature = "tokio")]
+async fn tokio_finish_entry() {
+	let mut data = Vec::new();
+	let mut writer = crate::tokio::Zip::new(&mut data);
+	let mut entry = writer
+		.create_entry("1.txt", EntryOptions::new().compression(Compression::None))
+		.await
+		.unwrap();
+	assert!(entry.write_all(b"Some data\n").await.is_ok());
+	let entry = writer.finish_entry().await.unwrap();
+	assert_eq!(entry.name(), "1.txt");
+	assert_eq!(entry.uncompressed_size(), 10);
+	assert!(matches!(writer.finish_entry().await, Err(Error::NoEntry)));
+	assert!(writer.finish().await.is_ok());
+	#[cfg(feature = "crc")]
+	assert_eq!(data, ONE_UNCOMPRESSED_ENTRY);
+	#[cfg(not(feature = "crc"))]
+	assert_eq!(data, one_uncompressed_entry_no_crc!());
+}
+
+#[tokio::test]
+#[cfg(all(feature = "tokio", unix))]
+async fn tokio_add_file() {
+	let path = env::temp_dir().join("zip-writer-tokio-add-file.txt");
+	fs::write(&path, b"Some data\n").unwrap();
+	fs::set_permissions(&path, Permissions::from_mode(0o640)).unwrap();
+	let mut data = Vec::new();
+	let mut writer = crate::tokio::Zip::new(&mut data);
+	let entry = writer
+		.add_file(
+			&path,
+			"1.txt",
+			EntryOptions::new().compression(Compression::None),
+		)
+		.await
+		.unwrap();
+	fs::remove_file(&path).unwrap();
+	assert_eq!(entry.name(), "1.txt");
+	assert_eq!(entry.uncompressed_size(), 10);
+	assert_eq!(entry.permissions(), Some(0o100640));
+	assert!(writer.finish().await.is_ok());
+}
+
+#[tokio::test]
+#[cfg(all(feature = "tokio", unix))]
+async fn tokio_add_dir_all() {
+	let root = create_tree("zip-writer-tokio-add-dir-all");
+	let mut writer = crate::tokio::Zip::new(Vec::new());
+	let filter = Filter::new().exclude("*.tmp");
+	assert!(writer.add_dir_all(&root, "out", &filter, EntryOptions::new()).await.is_ok());
+	let names = writer.entries().iter().map(|entry| entry.name()).collect::<Vec<_>>();
+	assert_eq!(
+		names,
+		[
+			"out/a.txt",
+			"out/b/",
+			"out/b/c/",
+			"out/b/d.txt",
+			"out/f.txt",
+			"out/g"
+		]
+	);
+	assert!(writer.finish().await.is_ok());
+	fs::remove_dir_all(&root).unwrap();
+}
+
+#[tokio::test]
+#[cfg(feature = "tokio")]
+async fn tokio_name_policy() {
+	let mut writer = crate::tokio::Zip::new(Vec::new());
+	assert!(matches!(
+		writer.create_entry("../a.txt", EntryOptions::new()).await,
+		Err(Error::InvalidName(_))
+	));
+	let mut writer = crate::tokio::Zip::new(Vec::new()).name_policy(NamePolicy::Normalize);
+	let entry = writer.create_entry("../a.txt", EntryOptions::new()).await.unwrap();
+	assert_eq!(entry.finish().await.unwrap().name(), "a.txt");
+}
+
+#[tokio::test]
+#[cfg(feature = "tokio")]
+async fn tokio_duplicates() {
+	let mut writer = crate::tokio::Zip::new(Vec::new()).duplicates(Duplicates::Reject, true);
+	assert!(writer.create_entry("a.txt", EntryOptions::new()).await.is_ok());
+	assert!(matches!(
+		writer.create_entry("A.TXT", EntryOptions::new()).await,
+		Err(Error::DuplicateName(_))
+	));
+	let mut writer = crate::tokio::Zip::new(Vec::new()).duplicates(Duplicates::Rename, false);
+	assert!(writer.create_entry("a.txt", EntryOptions::new()).await.is_ok());
+	let entry = writer.create_entry("a.txt", EntryOptions::new()).await.unwrap();
+	assert_eq!(entry.finish().await.unwrap().name(), "a (1).txt");
+}
+
+#[tokio::test]
+#[cfg(feature = "tokio")]
+async fn tokio_raw_name() {
+	let mut writer = crate::tokio::Zip::new(Vec::new());
+	let entry = writer.create_entry_raw(b"\x93\xFA.txt", false, EntryOptions::new()).await.unwrap();
+	assert_eq!(entry.finish().await.unwrap().name_bytes(), b"\x93\xFA.txt");
+	let (data, _) = writer.finish().await.unwrap();
+	assert_eq!(data[6..8], [0b00001000, 0b00000000]);
+	assert_eq!(data[30..36], *b"\x93\xFA.txt");
+}
+
+#[tokio::test]
+#[cfg(feature = "tokio")]
+async fn tokio_extra_fields() {
+	let mut writer = crate::tokio::Zip::new(Vec::new());
+	let options =
+		EntryOptions::new().compression(Compression::None).local_extra_field(0xBEEF, [0x03]);
+	assert!(writer.create_entry("1.txt", options).await.is_ok());
+	assert!(matches!(
+		writer.create_entry("2.txt", EntryOptions::new().extra_field(0x9901, [])).await,
+		Err(Error::ReservedExtraField(0x9901))
+	));
+	let (data, summary) = writer.finish().await.unwrap();
+	assert_eq!(data[28..30], [0x05, 0x00]);
+	let position = summary.central_directory_offset as usize;
+	assert_eq!(data[position + 30..position + 32], [0x00, 0x00]);
+}
+
+#[tokio::test]
+#[cfg(feature = "tokio")]
+async fn tokio_poisoned() {
+	let mut writer = crate::tokio::Zip::new(FailingWriter {
+		remaining: 45,
+		timeout: false,
+	});
+	let mut entry = writer
+		.create_entry("1.txt", EntryOptions::new().compression(Compression::None))
+		.await
+		.unwrap();
+	assert!(entry.write_all(b"Some data\n").await.is_ok());
+	assert!(matches!(
+		entry.finish().await,
+		Err(Error::Io(error)) if error.kind() == ErrorKind::BrokenPipe
+	));
+	assert!(matches!(
+		writer.create_entry("2.txt", EntryOptions::new().compression(Compression::None)).await,
+		Err(Error::Poisoned)
+	));
+	assert!(matches!(writer.finish_entry().await, Err(Error::Poisoned)));
+	assert!(matches!(writer.finish().await, Err(Error::Poisoned)));
+}
+
+#[tokio::test]
+#[cfg(feature = "tokio")]
+async fn tokio_append() {
+	let mut writer =
+		crate::tokio::Zip::append(Cursor::new(ONE_UNCOMPRESSED_ENTRY.to_vec())).await.unwrap();
+	assert_eq!(writer.entries().len(), 1);
+	let mut entry = writer
+		.create_entry("2.txt", EntryOptions::new().compression(Compression::None))
+		.await
+		.unwrap();
+	assert!(entry.write_all(b"Some more data\n").await.is_ok());
+	assert!(entry.finish().await.is_ok());
+	let (data, summary) = writer.finish().await.unwrap();
+	assert_eq!(summary.entries.len(), 2);
+	assert_eq!(summary.size, data.get_ref().len() as u64);
+	#[cfg(feature = "crc")]
+	assert_eq!(data.into_inner(), TWO_ENTRIES);
+	assert!(matches!(
+		crate::tokio::Zip::append(Cursor::new(Vec::new())).await,
+		Err(Error::InvalidArchive(_))
+	));
+}
+
+#[tokio::test]
+#[cfg(feature = "tokio")]
+async fn tokio_stream() {
+	let mut reader = read::AsyncStream::new(TWO_ENTRIES);
+	let mut entry = reader.next_entry().await.unwrap().unwrap();
+	let mut content = Vec::new();
+	assert!(entry.read_to_end(&mut content).await.is_ok());
+	assert_eq!(content, b"Some data\n");
+	assert_eq!(entry.entry().uncompressed_size(), 10);
+	assert_eq!(
+		reader.next_entry().await.unwrap().unwrap().entry().name(),
+		"2.txt"
+	);
+	assert!(reader.next_entry().await.unwrap().is_none());
+}
@@ -1,31 +1,53 @@
+#[cfg(feature = "aes")]
+use crate::aes::AesWriter;
 use crate::{
-	Compression, DateTime, Entry, CENTRAL_DIRECTORY_HEADER, END_CENTRAL_DIRECTORY, LOCAL_HEADER,
-	PLATFORM, VERSION,
+	central,
+	name::{self, EntryName, Names},
+	prepare_entry,
+	walk::{self, Kind},
+	Compression, DateTime, Duplicates, Encryption, Entry, EntryOptions, Error, Filter, Limit,
+	NamePolicy, Summary, CENTRAL_DIRECTORY_HEADER, DATA_DESCRIPTOR, END_CENTRAL_DIRECTORY,
+	LOCAL_HEADER,
 };
 #[cfg(feature = "deflate")]
-use async_compression::tokio::write::DeflateEncoder;
+use async_compression::{tokio::write::DeflateEncoder, Level};
 #[cfg(feature = "crc")]
 use crc32fast::Hasher;
 use std::{
-	io::{self, Error, ErrorKind},
-	mem::replace,
+	io::{self, SeekFrom},
+	mem::{replace, take},
+	path::Path,
 	pin::Pin,
 	task::{Context, Poll},
 };
-use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::{
+	fs::File,
+	io::{self as tokio_io, AsyncRead, AsyncSeek, AsyncSeekExt, AsyncWrite, AsyncWriteExt},
+};
 
 enum Writer<W: AsyncWrite + Unpin> {
+	#[cfg(feature = "aes")]
+	Aes(AesWriter<W>),
+	#[cfg(all(feature = "aes", feature = "deflate"))]
+	AesDeflate(DeflateEncoder<AesWriter<W>>),
 	#[cfg(feature = "deflate")]
 	Deflate(DeflateEncoder<W>),
 	Raw(W),
-	None,
+	Poisoned,
 }
 
 pub struct Zip<W: AsyncWrite + Unpin> {
 	#[cfg(feature = "crc")]
 	crc: Hasher,
+	// Comment of an archive that entries are appended to.
+	comment: Vec<u8>,
 	cursor: u64,
+	descriptor_signature: bool,
 	entries: Vec<Entry>,
+	entry: Option<Entry>,
+	epoch: Option<DateTime>,
+	name_policy: NamePolicy,
+	names: Names,
 	writer: Writer<W>,
 }
 
@@ -34,79 +56,221 @@ impl<W: AsyncWrite + Unpin> Zip<W> {
 		Self {
 			#[cfg(feature = "crc")]
 			crc: Hasher::new(),
+			comment: Vec::new(),
+			descriptor_signature: true,
 			entries: Vec::new(),
+			entry: None,
+			epoch: None,
+			name_policy: NamePolicy::default(),
+			names: Names::default(),
 			cursor: 0,
 			writer: Writer::Raw(writer),
 		}
 	}
 
+	// The signature of data descriptors is optional, but most readers expect it.
+	pub fn descriptor_signature(mut self, signature: bool) -> Self {
+		self.descriptor_signature = signature;
+		self
+	}
+
+	// Duplicate names are detected among the entries created by this writer. Comparisons can
+	// ignore case, as extracting to Windows or macOS file systems would.
+	pub fn duplicates(mut self, duplicates: Duplicates, case_insensitive: bool) -> Self {
+		self.names = Names::new(duplicates, case_insensitive);
+		for entry in &self.entries {
+			self.names.insert(&entry.name);
+		}
+		self
+	}
+
+	pub fn name_policy(mut self, policy: NamePolicy) -> Self {
+		self.name_policy = policy;
+		self
+	}
+
+	// Timestamps are clamped to SOURCE_DATE_EPOCH, or to 1980-01-01 when it is not set.
+	pub fn reproducible(self) -> Self {
+		self.reproducible_at(DateTime::source_date_epoch())
+	}
+
+	// Clamps timestamps to `epoch` and normalizes permissions, so that archiving the same content
+	// always yields the same bytes. Directory walks are always sorted.
+	pub fn reproducible_at(mut self, epoch: DateTime) -> Self {
+		self.epoch = Some(epoch);
+		self
+	}
+
 	pub async fn create_entry<T: Into<String>>(
 		&mut self,
 		name: T,
-		compression: Compression,
-		date_time: DateTime,
-	) -> io::Result<()> {
-		let name = name.into();
-		if name.len() > u16::MAX.into() {
-			return Err(Error::new(ErrorKind::InvalidInput, ""));
+		options: EntryOptions,
+	) -> Result<EntryWriter<'_, W>, Error> {
+		self.start_entry(EntryName::Text(name.into()), options).await?;
+		Ok(EntryWriter { zip: self })
+	}
+
+	// Names are written as given, without conversion. When `utf8` is false, readers interpret
+	// them as CP437 or in the encoding of their system.
+	pub async fn create_entry_raw<T: Into<Vec<u8>>>(
+		&mut self,
+		name: T,
+		utf8: bool,
+		options: EntryOptions,
+	) -> Result<EntryWriter<'_, W>, Error> {
+		self.start_entry(EntryName::Raw(name.into(), utf8), options).await?;
+		Ok(EntryWriter { zip: self })
+	}
+
+	pub async fn add_file<P: AsRef<Path>, T: Into<String>>(
+		&mut self,
+		path: P,
+		name: T,
+		options: EntryOptions,
+	) -> Result<Entry, Error> {
+		let mut file = File::open(path).await?;
+		let options = options.metadata(&file.metadata().await?);
+		let mut entry = self.create_entry(name, options).await?;
+		tokio_io::copy(&mut file, &mut entry).await?;
+		entry.finish().await
+	}
+
+	pub async fn add_dir_all<P: AsRef<Path>>(
+		&mut self,
+		root: P,
+		prefix: &str,
+		filter: &Filter,
+		options: EntryOptions,
+	) -> Result<(), Error> {
+		for item in walk::walk_async(root.as_ref(), filter).await? {
+			let name = walk::join(prefix, &item.name);
+			match item.kind {
+				Kind::Directory => {
+					let options = options
+						.clone()
+						.compression(Compression::None)
+						.encryption(Encryption::None)
+						.metadata(&item.metadata);
+					self.create_entry(name + "/", options).await?.finish().await?;
+				}
+				Kind::File => {
+					self.add_file(&item.path, name, options.clone()).await?;
+				}
+				Kind::Symlink(target) => {
+					name::check_target(self.name_policy, &name, &target)?;
+					let options = options
+						.clone()
+						.compression(Compression::None)
+						.encryption(Encryption::None)
+						.metadata(&item.metadata);
+					let mut entry = self.create_entry(name, options).await?;
+					entry.write_all(target.as_bytes()).await?;
+					entry.finish().await?;
+				}
+			}
 		}
+
+		Ok(())
+	}
+
+	async fn start_entry(&mut self, name: EntryName, options: EntryOptions) -> Result<(), Error> {
+		#[cfg(feature = "deflate")]
+		let level = options.level.map_or(Level::Default, |level| Level::Precise(level as i32));
+		let (mut entry, local_extra_field) =
+			prepare_entry(&self.names, self.name_policy, self.epoch, name, &options)?;
 		let mut writer = self.commit_previous().await?;
+		self.names.insert(&entry.name);
+		if self.cursor > u32::MAX.into() {
+			return Err(Error::LimitExceeded(Limit::Offset));
+		}
 		writer.write_all(LOCAL_HEADER).await?;
-		writer.write_all(VERSION).await?;
-		writer.write_all(&[0b00001000, 0b00001000]).await?;
-		writer.write_all(&compression.to_le_bytes()).await?;
-		writer.write_all(&date_time.to_le_bytes()).await?;
+		writer.write_all(&entry.version.to_le_bytes()).await?;
+		writer.write_all(&entry.flags.to_le_bytes()).await?;
+		writer.write_all(&entry.method.to_le_bytes()).await?;
+		writer.write_all(&entry.date_time.to_le_bytes()).await?;
 		writer.write_all(&[0x00, 0x00, 0x00, 0x00]).await?;
 		writer.write_all(&[0x00, 0x00, 0x00, 0x00]).await?;
 		writer.write_all(&[0x00, 0x00, 0x00, 0x00]).await?;
-		writer.write_all(&(name.len() as u16).to_le_bytes()).await?;
-		writer.write_all(&[0x00, 0x00]).await?;
-		writer.write_all(name.as_bytes()).await?;
+		writer.write_all(&(entry.raw_name.len() as u16).to_le_bytes()).await?;
+		writer.write_all(&(local_extra_field.len() as u16).to_le_bytes()).await?;
+		writer.write_all(&entry.raw_name).await?;
+		writer.write_all(&local_extra_field).await?;
 		_ = replace(
 			&mut self.writer,
-			match compression {
+			match (options.compression, options.encryption) {
+				#[cfg(all(feature = "aes", feature = "deflate"))]
+				(
+					Compression::Deflate,
+					Encryption::Aes {
+						password,
+						strength,
+						version,
+					},
+				) => Writer::AesDeflate(DeflateEncoder::with_quality(
+					AesWriter::new(writer, &password, &strength, version)
+						.map_err(Error::Encoder)?,
+					level,
+				)),
+				#[cfg(feature = "aes")]
+				(
+					Compression::None,
+					Encryption::Aes {
+						password,
+						strength,
+						version,
+					},
+				) => Writer::Aes(
+					AesWriter::new(writer, &password, &strength, version)
+						.map_err(Error::Encoder)?,
+				),
 				#[cfg(feature = "deflate")]
-				Compression::Deflate => Writer::Deflate(DeflateEncoder::new(writer)),
-				Compression::None => Writer::Raw(writer),
+				(Compression::Deflate, Encryption::None) => {
+					Writer::Deflate(DeflateEncoder::with_quality(writer, level))
+				}
+				(Compression::None, Encryption::None) => Writer::Raw(writer),
 			},
 		);
-		let position = self.cursor;
-		self.cursor += 30 + name.len() as u64;
-		self.entries.push(Entry {
-			compression,
-			crc: 0,
-			date_time,
-			name,
-			position,
-			raw_size: 0,
-			size: self.cursor,
-		});
+		entry.position = self.cursor;
+		self.cursor += 30 + entry.raw_name.len() as u64 + local_extra_field.len() as u64;
+		entry.size = self.cursor;
+		self.entry = Some(entry);
 
 		Ok(())
 	}
 
-	pub async fn finish(&mut self) -> io::Result<()> {
+	pub async fn finish(mut self) -> Result<(W, Summary), Error> {
 		let mut writer = self.commit_previous().await?;
+		if self.entries.len() > u16::MAX.into() {
+			return Err(Error::LimitExceeded(Limit::Entries));
+		}
 		let position = self.cursor;
 		for entry in &self.entries {
 			writer.write_all(CENTRAL_DIRECTORY_HEADER).await?;
-			writer.write_all(PLATFORM).await?;
-			writer.write_all(VERSION).await?;
-			writer.write_all(&[0b00001000, 0b00001000]).await?;
-			writer.write_all(&entry.compression.to_le_bytes()).await?;
+			writer.write_all(&entry.version_made_by.to_le_bytes()).await?;
+			writer.write_all(&entry.version.to_le_bytes()).await?;
+			writer.write_all(&entry.flags.to_le_bytes()).await?;
+			writer.write_all(&entry.method.to_le_bytes()).await?;
 			writer.write_all(&entry.date_time.to_le_bytes()).await?;
 			writer.write_all(&entry.crc.to_le_bytes()).await?;
 			writer.write_all(&(entry.size as u32).to_le_bytes()).await?;
 			writer.write_all(&(entry.raw_size as u32).to_le_bytes()).await?;
-			writer.write_all(&(entry.name.len() as u16).to_le_bytes()).await?;
-			writer.write_all(&[0x00, 0x00]).await?;
-			writer.write_all(&[0x00, 0x00]).await?;
+			writer.write_all(&(entry.raw_name.len() as u16).to_le_bytes()).await?;
+			writer.write_all(&(entry.extra_field.len() as u16).to_le_bytes()).await?;
+			writer.write_all(&(entry.raw_comment.len() as u16).to_le_bytes()).await?;
 			writer.write_all(&[0x00, 0x00]).await?;
-			writer.write_all(&[0x00, 0x00]).await?;
-			writer.write_all(&[0x00, 0x00, 0x00, 0x00]).await?;
+			writer.write_all(&entry.internal_attributes.to_le_bytes()).await?;
+			writer.write_all(&entry.external_attributes.to_le_bytes()).await?;
 			writer.write_all(&(entry.position as u32).to_le_bytes()).await?;
-			writer.write_all(entry.name.as_bytes()).await?;
-			self.cursor += 46 + entry.name.len() as u64;
+			writer.write_all(&entry.raw_name).await?;
+			writer.write_all(&entry.extra_field).await?;
+			writer.write_all(&entry.raw_comment).await?;
+			self.cursor += 46
+				+ entry.raw_name.len() as u64
+				+ entry.extra_field.len() as u64
+				+ entry.raw_comment.len() as u64;
+		}
+		if self.cursor > u32::MAX.into() {
+			return Err(Error::LimitExceeded(Limit::Offset));
 		}
 		let number_entries = self.entries.len() as u16;
 		let size = (self.cursor - position) as u32;
@@ -117,19 +281,47 @@ impl<W: AsyncWrite + Unpin> Zip<W> {
 		writer.write_all(&number_entries.to_le_bytes()).await?;
 		writer.write_all(&size.to_le_bytes()).await?;
 		writer.write_all(&(position as u32).to_le_bytes()).await?;
-		writer.write_all(&[0x00, 0x00]).await?;
+		writer.write_all(&(self.comment.len() as u16).to_le_bytes()).await?;
+		writer.write_all(&self.comment).await?;
+		self.cursor += 22 + self.comment.len() as u64;
 
-		Ok(())
+		Ok((
+			writer,
+			Summary {
+				central_directory_offset: position,
+				entries: take(&mut self.entries),
+				size: self.cursor,
+			},
+		))
+	}
+
+	pub async fn finish_entry(&mut self) -> Result<&Entry, Error> {
+		if let Writer::Poisoned = self.writer {
+			return Err(Error::Poisoned);
+		}
+		if self.entry.is_none() {
+			return Err(Error::NoEntry);
+		}
+		self.writer = Writer::Raw(self.commit_previous().await?);
+		Ok(&self.entries[self.entries.len() - 1])
+	}
+
+	pub fn entries(&self) -> &[Entry] {
+		&self.entries
 	}
 
-	async fn commit_previous(&mut self) -> io::Result<W> {
-		let writer = replace(&mut self.writer, Writer::None);
-		let Some(entry) = self.entries.last_mut() else {
+	async fn commit_previous(&mut self) -> Result<W, Error> {
+		let writer = replace(&mut self.writer, Writer::Poisoned);
+		let Some(mut entry) = self.entry.take() else {
 			return Ok(match writer {
+				#[cfg(feature = "aes")]
+				Writer::Aes(_) => return Err(Error::Poisoned),
+				#[cfg(all(feature = "aes", feature = "deflate"))]
+				Writer::AesDeflate(_) => return Err(Error::Poisoned),
 				#[cfg(feature = "deflate")]
 				Writer::Deflate(encoder) => encoder.into_inner(),
 				Writer::Raw(writer) => writer,
-				Writer::None => unreachable!(),
+				Writer::Poisoned => return Err(Error::Poisoned),
 			});
 		};
 		#[cfg(feature = "crc")]
@@ -140,61 +332,124 @@ impl<W: AsyncWrite + Unpin> Zip<W> {
 		let start = entry.size;
 		entry.raw_size = self.cursor - start;
 		let (mut writer, size) = match writer {
+			#[cfg(feature = "aes")]
+			Writer::Aes(mut writer) => {
+				writer.flush().await.map_err(Error::Encoder)?;
+				if writer.hides_crc() {
+					entry.crc = 0;
+				}
+				let (mut writer, code, size) = writer.into_parts();
+				writer.write_all(&code).await?;
+				(writer, size)
+			}
+			#[cfg(all(feature = "aes", feature = "deflate"))]
+			Writer::AesDeflate(mut encoder) => {
+				encoder.shutdown().await.map_err(Error::Encoder)?;
+				let writer = encoder.into_inner();
+				if writer.hides_crc() {
+					entry.crc = 0;
+				}
+				let (mut writer, code, size) = writer.into_parts();
+				writer.write_all(&code).await?;
+				(writer, size)
+			}
 			#[cfg(feature = "deflate")]
 			Writer::Deflate(mut encoder) => {
-				encoder.flush().await?;
-				encoder.shutdown().await?;
+				encoder.flush().await.map_err(Error::Encoder)?;
+				encoder.shutdown().await.map_err(Error::Encoder)?;
 				let size = encoder.total_out();
 				(encoder.into_inner(), size)
 			}
 			Writer::Raw(writer) => (writer, entry.raw_size),
-			Writer::None => unreachable!(),
+			Writer::Poisoned => return Err(Error::Poisoned),
 		};
 		entry.size = size;
+		if entry.size > u32::MAX.into() || entry.raw_size > u32::MAX.into() {
+			return Err(Error::LimitExceeded(Limit::Size));
+		}
+		if self.descriptor_signature {
+			writer.write_all(DATA_DESCRIPTOR).await?;
+		}
 		writer.write_all(&entry.crc.to_le_bytes()).await?;
 		writer.write_all(&(entry.size as u32).to_le_bytes()).await?;
 		writer.write_all(&(entry.raw_size as u32).to_le_bytes()).await?;
 		self.cursor = start + entry.size + 12;
+		if self.descriptor_signature {
+			self.cursor += 4;
+		}
+		self.entries.push(entry);
 
 		Ok(writer)
 	}
 }
 
-impl<W: AsyncWrite + Unpin> AsyncWrite for Zip<W> {
+impl<W: AsyncRead + AsyncWrite + AsyncSeek + Unpin> Zip<W> {
+	// Entries are added after those of an existing archive, writing over its central directory.
+	// The archive comment is kept, so the new archive is never shorter than the old one.
+	pub async fn append(mut writer: W) -> Result<Self, Error> {
+		let (end, entries) = central::read_async(&mut writer).await?;
+		writer.seek(SeekFrom::Start(end.offset)).await?;
+		Ok(Self {
+			comment: end.comment,
+			cursor: end.offset,
+			entries,
+			..Self::new(writer)
+		})
+	}
+}
+
+// Dropping an entry writer without calling `finish` leaves the entry open until the next call to
+// `create_entry`, `finish_entry` or `finish`, as committing it requires asynchronous writes.
+pub struct EntryWriter<'a, W: AsyncWrite + Unpin> {
+	zip: &'a mut Zip<W>,
+}
+
+impl<W: AsyncWrite + Unpin> EntryWriter<'_, W> {
+	pub async fn finish(self) -> Result<Entry, Error> {
+		self.zip.finish_entry().await.cloned()
+	}
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for EntryWriter<'_, W> {
 	fn poll_write(
-		mut self: Pin<&mut Self>,
+		self: Pin<&mut Self>,
 		context: &mut Context<'_>,
 		data: &[u8],
 	) -> Poll<io::Result<usize>> {
-		let status = match &mut self.writer {
+		let zip = &mut *self.get_mut().zip;
+		let status = match &mut zip.writer {
+			#[cfg(feature = "aes")]
+			Writer::Aes(writer) => Pin::new(writer).poll_write(context, data),
+			#[cfg(all(feature = "aes", feature = "deflate"))]
+			Writer::AesDeflate(writer) => Pin::new(writer).poll_write(context, data),
 			#[cfg(feature = "deflate")]
 			Writer::Deflate(writer) => Pin::new(writer).poll_write(context, data),
 			Writer::Raw(writer) => Pin::new(writer).poll_write(context, data),
-			Writer::None => unreachable!(),
+			Writer::Poisoned => Poll::Ready(Err(Error::Poisoned.into())),
 		};
 		if let Poll::Ready(Ok(size)) = status {
 			#[cfg(feature = "crc")]
-			self.crc.update(data);
-			self.cursor += size as u64;
+			zip.crc.update(&data[..size]);
+			zip.cursor += size as u64;
 		}
 		status
 	}
 
-	fn poll_flush(mut self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<io::Result<()>> {
-		match &mut self.writer {
+	fn poll_flush(self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<io::Result<()>> {
+		match &mut self.get_mut().zip.writer {
+			#[cfg(feature = "aes")]
+			Writer::Aes(writer) => Pin::new(writer).poll_flush(context),
+			#[cfg(all(feature = "aes", feature = "deflate"))]
+			Writer::AesDeflate(writer) => Pin::new(writer).poll_flush(context),
 			#[cfg(feature = "deflate")]
 			Writer::Deflate(writer) => Pin::new(writer).poll_flush(context),
 			Writer::Raw(writer) => Pin::new(writer).poll_flush(context),
-			Writer::None => unreachable!(),
+			Writer::Poisoned => Poll::Ready(Err(Error::Poisoned.into())),
 		}
 	}
 
-	fn poll_shutdown(mut self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<io::Result<()>> {
-		match &mut self.writer {
-			#[cfg(feature = "deflate")]
-			Writer::Deflate(writer) => Pin::new(writer).poll_shutdown(context),
-			Writer::Raw(writer) => Pin::new(writer).poll_shutdown(context),
-			Writer::None => unreachable!(),
-		}
+	// The compressed stream is only terminated when the entry is committed.
+	fn poll_shutdown(self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<io::Result<()>> {
+		self.poll_flush(context)
 	}
 }
@@ -0,0 +1,131 @@
+use crate::{
+	central::read_u16, cp437, extra, name::EntryName, normalize_permissions, read, Entry,
+	EntryOptions, Error, Limit, Writer, Zip, DATA_DESCRIPTOR, FLAG_DESCRIPTOR, FLAG_UTF8,
+	LOCAL_HEADER,
+};
+use std::io::{self, Read, Seek, Write};
+
+pub enum Action {
+	Drop,
+	Keep,
+	Rename(String),
+	// The entry is written again with the given contents.
+	Replace(Vec<u8>, EntryOptions),
+}
+
+impl<W: Write> Zip<W> {
+	// Entries of `source` are added in order. Kept and renamed entries are copied without being
+	// decompressed.
+	pub fn transform<R, F>(&mut self, source: R, mut action: F) -> Result<(), Error>
+	where
+		R: Read + Seek,
+		F: FnMut(&Entry) -> Action,
+	{
+		let mut source = read::Zip::new(source)?;
+		for index in 0..source.entries().len() {
+			match action(&source.entries()[index]) {
+				Action::Drop => {}
+				Action::Keep => _ = self.copy_entry(&mut source, index, None)?,
+				Action::Rename(name) => _ = self.copy_entry(&mut source, index, Some(name))?,
+				Action::Replace(data, options) => {
+					let mut entry = self.create_entry(source.entries()[index].name(), options)?;
+					entry.write_all(&data)?;
+					entry.finish()?;
+				}
+			}
+		}
+
+		Ok(())
+	}
+
+	// The compressed data is written as stored in `source`, encrypted entries included.
+	pub fn copy_entry<R: Read + Seek>(
+		&mut self,
+		source: &mut read::Zip<R>,
+		index: usize,
+		name: Option<String>,
+	) -> Result<Entry, Error> {
+		let mut entry = source.entries()[index].clone();
+		let (header, mut data) = source.raw(index)?;
+		let mut local_extra_field = header[30 + usize::from(read_u16(&header, 26))..].to_vec();
+		let name = match name {
+			Some(name) => EntryName::Text(name),
+			None => EntryName::Raw(entry.raw_name.clone(), entry.flags & FLAG_UTF8 != 0),
+		};
+		let (name, raw_name) = self.names.resolve(self.name_policy, name)?;
+		// Renamed entries are written in UTF-8, Unicode extra fields would contradict them.
+		if raw_name.is_none() {
+			if name.len() > u16::MAX.into() {
+				return Err(Error::NameTooLong(name.len()));
+			}
+			if entry.flags & FLAG_UTF8 == 0 {
+				entry.raw_comment = entry.comment.as_bytes().to_vec();
+				if entry.raw_comment.len() > u16::MAX.into() {
+					return Err(Error::CommentTooLong(entry.raw_comment.len()));
+				}
+			}
+			entry.flags |= FLAG_UTF8;
+			entry.raw_name = name.as_bytes().to_vec();
+			for id in [cp437::UNICODE_COMMENT, cp437::UNICODE_PATH] {
+				local_extra_field = extra::remove(&local_extra_field, id);
+				entry.extra_field = extra::remove(&entry.extra_field, id);
+			}
+		}
+		entry.name = name;
+		if let Some(epoch) = self.epoch {
+			entry.date_time = entry.date_time.min(epoch);
+			// Timestamps stored in extra fields would still differ between builds.
+			for id in [extra::EXTENDED_TIMESTAMP, extra::NTFS] {
+				local_extra_field = extra::remove(&local_extra_field, id);
+				entry.extra_field = extra::remove(&entry.extra_field, id);
+			}
+			if let Some(mode) = entry.permissions {
+				let mode = normalize_permissions(mode);
+				entry.permissions = Some(mode);
+				entry.external_attributes = mode << 16 | entry.external_attributes & 0xFFFF;
+			}
+		}
+		let mut writer = self.commit_previous()?;
+		self.names.insert(&entry.name);
+		if self.cursor > u32::MAX.into() {
+			return Err(Error::LimitExceeded(Limit::Offset));
+		}
+		let descriptor = entry.flags & FLAG_DESCRIPTOR != 0;
+		let (crc, size, raw_size) = match descriptor {
+			true => (0, 0, 0),
+			false => (entry.crc, entry.size as u32, entry.raw_size as u32),
+		};
+		writer.write_all(LOCAL_HEADER)?;
+		writer.write_all(&entry.version.to_le_bytes())?;
+		writer.write_all(&entry.flags.to_le_bytes())?;
+		writer.write_all(&entry.method.to_le_bytes())?;
+		writer.write_all(&entry.date_time.to_le_bytes())?;
+		writer.write_all(&crc.to_le_bytes())?;
+		writer.write_all(&size.to_le_bytes())?;
+		writer.write_all(&raw_size.to_le_bytes())?;
+		writer.write_all(&(entry.raw_name.len() as u16).to_le_bytes())?;
+		writer.write_all(&(local_extra_field.len() as u16).to_le_bytes())?;
+		writer.write_all(&entry.raw_name)?;
+		writer.write_all(&local_extra_field)?;
+		if io::copy(&mut data, &mut writer)? != entry.size {
+			return Err(Error::InvalidArchive("entry data is truncated"));
+		}
+		entry.position = self.cursor;
+		self.cursor +=
+			30 + entry.raw_name.len() as u64 + local_extra_field.len() as u64 + entry.size;
+		if descriptor {
+			if self.descriptor_signature {
+				writer.write_all(DATA_DESCRIPTOR)?;
+				self.cursor += 4;
+			}
+			writer.write_all(&entry.crc.to_le_bytes())?;
+			writer.write_all(&(entry.size as u32).to_le_bytes())?;
+			writer.write_all(&(entry.raw_size as u32).to_le_bytes())?;
+			self.cursor += 12;
+		}
+		self.writer = Writer::Raw(writer);
+		self.entries.push(entry.clone());
+
+		Ok(entry)
+	}
+}
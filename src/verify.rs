@@ -0,0 +1,128 @@
+use crate::{
+	central::{read_u16, read_u32},
+	name::{self, Names},
+	read::{self, descriptor_length},
+	Duplicates, Error, NamePolicy, DATA_DESCRIPTOR, FLAG_DESCRIPTOR, FLAG_ENCRYPTED,
+};
+use std::io::{self, Read, Seek};
+
+pub struct Problem {
+	// Name of the entry concerned.
+	pub entry: String,
+	pub error: Error,
+}
+
+pub struct Report {
+	pub entries: usize,
+	pub problems: Vec<Problem>,
+	// Entries whose contents could not be checked, as they are encrypted or compressed with an
+	// unsupported method.
+	pub unchecked: Vec<String>,
+}
+
+impl Report {
+	pub fn is_valid(&self) -> bool {
+		self.problems.is_empty()
+	}
+}
+
+// Archives whose end of central directory or central directory cannot be read are refused with
+// an error, other problems are collected in the report.
+pub fn verify<R: Read + Seek>(reader: R) -> Result<Report, Error> {
+	let mut zip = read::Zip::new(reader)?;
+	let entries = zip.entries().to_vec();
+	let mut names = Names::new(Duplicates::Reject, false);
+	let mut problems = Vec::new();
+	let mut spans = Vec::new();
+	let mut unchecked = Vec::new();
+	for (index, entry) in entries.iter().enumerate() {
+		let mut problem = |error| {
+			problems.push(Problem {
+				entry: entry.name.clone(),
+				error,
+			})
+		};
+		if let Err(error) =
+			name::check(NamePolicy::Reject, entry.name.clone()).and_then(|name| names.check(name))
+		{
+			problem(error);
+		}
+		names.insert(&entry.name);
+		if entry.position + 30 > zip.central_directory_offset {
+			problem(Error::InvalidArchive("local header is out of bounds"));
+			continue;
+		}
+		let header = match zip.local_header(entry.position) {
+			Ok(header) => header,
+			Err(error) => {
+				problem(error);
+				continue;
+			}
+		};
+		let descriptor = entry.flags & FLAG_DESCRIPTOR != 0;
+		// Values deferred to a data descriptor may be left as zeros in the local header.
+		let matches = |offset, value: u64| {
+			let field = read_u32(&header, offset);
+			u64::from(field) == value || descriptor && field == 0
+		};
+		let name_length = usize::from(read_u16(&header, 26));
+		if read_u16(&header, 4) != entry.version
+			|| read_u16(&header, 6) != entry.flags
+			|| read_u16(&header, 8) != entry.method
+			|| header[10..14] != entry.date_time.to_le_bytes()
+			|| header[30..30 + name_length] != entry.raw_name
+			|| !matches(14, entry.crc.into())
+			|| !matches(18, entry.size)
+			|| !matches(22, entry.raw_size)
+		{
+			problem(Error::InvalidArchive(
+				"local header does not match the central directory",
+			));
+		}
+		let start = entry.position + header.len() as u64;
+		let mut end = start + entry.size;
+		if descriptor && end + 16 <= zip.central_directory_offset {
+			let data = zip.read_at(end, 16)?;
+			end += match descriptor_length(&data, Some(entry.crc), entry.size, entry.raw_size) {
+				Some(length) => length as u64,
+				None if data.starts_with(DATA_DESCRIPTOR) => 16,
+				None => 12,
+			};
+		} else if descriptor {
+			end += 12;
+		}
+		if end > zip.central_directory_offset {
+			problem(Error::InvalidArchive("entry data is out of bounds"));
+			continue;
+		}
+		spans.push((entry.position, end, index));
+		let supported = entry.method == 0 || cfg!(feature = "deflate") && entry.method == 8;
+		if entry.flags & FLAG_ENCRYPTED != 0 || !supported {
+			unchecked.push(entry.name.clone());
+			continue;
+		}
+		let copied =
+			zip.entry(index).and_then(|mut data| Ok(io::copy(&mut data, &mut io::sink())?));
+		if let Err(error) = copied {
+			problem(error);
+		}
+	}
+	// A span can cover several of the following ones, so the furthest end so far is kept.
+	spans.sort();
+	let mut furthest = 0;
+	for (start, end, index) in spans {
+		if start < furthest {
+			problems.push(Problem {
+				entry: entries[index].name.clone(),
+				error: Error::InvalidArchive("entry overlaps another entry"),
+			});
+		}
+		furthest = furthest.max(end);
+	}
+
+	Ok(Report {
+		entries: entries.len(),
+		problems,
+		unchecked,
+	})
+}
@@ -0,0 +1,224 @@
+use crate::Error;
+use std::{
+	collections::HashSet,
+	ffi::OsStr,
+	fs::{self, Metadata},
+	path::{Path, PathBuf},
+};
+
+// Patterns support `?`, `*` within a path component and `**` across components. Patterns
+// without a slash are matched against the file name only, others against the whole path
+// relative to the root.
+#[derive(Clone, Default)]
+pub struct Filter {
+	exclude: Vec<String>,
+	follow_symlinks: bool,
+	include: Vec<String>,
+}
+
+impl Filter {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn exclude<T: Into<String>>(mut self, pattern: T) -> Self {
+		self.exclude.push(pattern.into());
+		self
+	}
+
+	// Symbolic links are stored as links unless they are followed.
+	pub fn follow_symlinks(mut self, follow: bool) -> Self {
+		self.follow_symlinks = follow;
+		self
+	}
+
+	// Without include patterns every file is added. Directories are only subject to exclusion.
+	pub fn include<T: Into<String>>(mut self, pattern: T) -> Self {
+		self.include.push(pattern.into());
+		self
+	}
+
+	fn accepts(&self, name: &str, directory: bool) -> bool {
+		if self.exclude.iter().any(|pattern| matches(pattern, name)) {
+			return false;
+		}
+		directory
+			|| self.include.is_empty()
+			|| self.include.iter().any(|pattern| matches(pattern, name))
+	}
+}
+
+pub(crate) enum Kind {
+	Directory,
+	File,
+	Symlink(String),
+}
+
+pub(crate) struct Item {
+	pub(crate) kind: Kind,
+	pub(crate) metadata: Metadata,
+	pub(crate) name: String,
+	pub(crate) path: PathBuf,
+}
+
+pub(crate) fn join(prefix: &str, name: &str) -> String {
+	match prefix.trim_end_matches('/') {
+		"" => name.to_string(),
+		prefix => format!("{prefix}/{name}"),
+	}
+}
+
+pub(crate) fn matches(pattern: &str, name: &str) -> bool {
+	let name = match pattern.contains('/') {
+		true => name,
+		false => name.rsplit('/').next().unwrap_or(name),
+	};
+	let pattern = pattern.chars().collect::<Vec<_>>();
+	let name = name.chars().collect::<Vec<_>>();
+	matches_chars(&pattern, &name)
+}
+
+fn matches_chars(pattern: &[char], name: &[char]) -> bool {
+	match pattern {
+		[] => name.is_empty(),
+		['*', '*'] => true,
+		['*', '*', rest @ ..] => {
+			let rest = rest.strip_prefix(&['/']).unwrap_or(rest);
+			(0..=name.len())
+				.filter(|&i| i == 0 || name[i - 1] == '/')
+				.any(|i| matches_chars(rest, &name[i..]))
+		}
+		['*', rest @ ..] => (0..=name.len())
+			.take_while(|&i| i == 0 || name[i - 1] != '/')
+			.any(|i| matches_chars(rest, &name[i..])),
+		['?', rest @ ..] => {
+			name.first().is_some_and(|&c| c != '/') && matches_chars(rest, &name[1..])
+		}
+		[c, rest @ ..] => name.first() == Some(c) && matches_chars(rest, &name[1..]),
+	}
+}
+
+fn to_name(name: &OsStr) -> Result<&str, Error> {
+	name.to_str().ok_or_else(|| Error::InvalidName(name.to_string_lossy().into_owned()))
+}
+
+// Entries are listed depth first in name order, so that archives do not depend on the order in
+// which the file system returns them.
+pub(crate) fn walk(root: &Path, filter: &Filter) -> Result<Vec<Item>, Error> {
+	let mut items = Vec::new();
+	let mut visited = HashSet::from([fs::canonicalize(root)?]);
+	let mut stack = children(root, "")?;
+	while let Some((path, name)) = stack.pop() {
+		let mut metadata = fs::symlink_metadata(&path)?;
+		if metadata.is_symlink() {
+			if !filter.follow_symlinks {
+				if filter.accepts(&name, false) {
+					let target = fs::read_link(&path)?;
+					let target = to_name(target.as_os_str())?.to_string();
+					items.push(Item {
+						kind: Kind::Symlink(target),
+						metadata,
+						name,
+						path,
+					});
+				}
+				continue;
+			}
+			metadata = fs::metadata(&path)?;
+		}
+		if metadata.is_dir() {
+			if !filter.accepts(&name, true) || !visited.insert(fs::canonicalize(&path)?) {
+				continue;
+			}
+			stack.extend(children(&path, &name)?);
+			items.push(Item {
+				kind: Kind::Directory,
+				metadata,
+				name,
+				path,
+			});
+		} else if filter.accepts(&name, false) {
+			items.push(Item {
+				kind: Kind::File,
+				metadata,
+				name,
+				path,
+			});
+		}
+	}
+
+	Ok(items)
+}
+
+// Children are returned in reverse order, ready to be pushed onto the stack.
+fn children(path: &Path, name: &str) -> Result<Vec<(PathBuf, String)>, Error> {
+	let mut children = Vec::new();
+	for child in fs::read_dir(path)? {
+		let child = child?;
+		let child_name = join(name, to_name(&child.file_name())?);
+		children.push((child.path(), child_name));
+	}
+	children.sort_by(|a, b| b.1.cmp(&a.1));
+	Ok(children)
+}
+
+#[cfg(feature = "tokio")]
+pub(crate) async fn walk_async(root: &Path, filter: &Filter) -> Result<Vec<Item>, Error> {
+	use tokio::fs;
+
+	let mut items = Vec::new();
+	let mut visited = HashSet::from([fs::canonicalize(root).await?]);
+	let mut stack = children_async(root, "").await?;
+	while let Some((path, name)) = stack.pop() {
+		let mut metadata = fs::symlink_metadata(&path).await?;
+		if metadata.is_symlink() {
+			if !filter.follow_symlinks {
+				if filter.accepts(&name, false) {
+					let target = fs::read_link(&path).await?;
+					let target = to_name(target.as_os_str())?.to_string();
+					items.push(Item {
+						kind: Kind::Symlink(target),
+						metadata,
+						name,
+						path,
+					});
+				}
+				continue;
+			}
+			metadata = fs::metadata(&path).await?;
+		}
+		if metadata.is_dir() {
+			if !filter.accepts(&name, true) || !visited.insert(fs::canonicalize(&path).await?) {
+				continue;
+			}
+			stack.extend(children_async(&path, &name).await?);
+			items.push(Item {
+				kind: Kind::Directory,
+				metadata,
+				name,
+				path,
+			});
+		} else if filter.accepts(&name, false) {
+			items.push(Item {
+				kind: Kind::File,
+				metadata,
+				name,
+				path,
+			});
+		}
+	}
+
+	Ok(items)
+}
+
+#[cfg(feature = "tokio")]
+async fn children_async(path: &Path, name: &str) -> Result<Vec<(PathBuf, String)>, Error> {
+	let mut children = Vec::new();
+	let mut directory = tokio::fs::read_dir(path).await?;
+	while let Some(child) = directory.next_entry().await? {
+		let child_name = join(name, to_name(&child.file_name())?);
+		children.push((child.path(), child_name));
+	}
+	children.sort_by(|a, b| b.1.cmp(&a.1));
+	Ok(children)
+}